@@ -0,0 +1,214 @@
+// Arpeggiator, plays the notes of a chord one at a time on a tempo synced grid
+// instead of all at once. play_chord hands it the voiced chord and the main
+// loop asks it each time round whether the next note is due.
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+pub const TEMPO_MIN: u16 = 40;
+pub const TEMPO_MAX: u16 = 240;
+pub const OCTAVES_MAX: u16 = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    // chords play all at once
+    #[default]
+    Off,
+    Up,
+    Down,
+    // up then back down without playing the top and bottom twice
+    UpDown,
+    Random,
+    // the order the voicing left the notes in
+    AsPlayed,
+}
+
+const PATTERNS: [Pattern; 6] = [Pattern::Off, Pattern::Up, Pattern::Down, Pattern::UpDown, Pattern::Random, Pattern::AsPlayed];
+
+impl Pattern {
+    pub fn next(self, steps: i64) -> Self {
+        let cur_idx = PATTERNS.iter().position(|pattern| *pattern == self).unwrap_or(0) as i64;
+        PATTERNS[(cur_idx + steps).rem_euclid(PATTERNS.len() as i64) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pattern::Off => "Off",
+            Pattern::Up => "Up",
+            Pattern::Down => "Down",
+            Pattern::UpDown => "Up-Down",
+            Pattern::Random => "Random",
+            Pattern::AsPlayed => "As Played",
+        }
+    }
+
+    pub fn abbrev(self) -> &'static str {
+        match self {
+            Pattern::Off => "Off",
+            Pattern::Up => "Up",
+            Pattern::Down => "Down",
+            Pattern::UpDown => "UpDn",
+            Pattern::Random => "Rand",
+            Pattern::AsPlayed => "Play",
+        }
+    }
+}
+
+// Length of a step in beats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Rate {
+    Quarter,
+    #[default]
+    Eighth,
+    EighthTriplet,
+    Sixteenth,
+}
+
+const RATES: [Rate; 4] = [Rate::Quarter, Rate::Eighth, Rate::EighthTriplet, Rate::Sixteenth];
+
+impl Rate {
+    pub fn next(self, steps: i64) -> Self {
+        let cur_idx = RATES.iter().position(|rate| *rate == self).unwrap_or(0) as i64;
+        RATES[(cur_idx + steps).rem_euclid(RATES.len() as i64) as usize]
+    }
+
+    pub fn abbrev(self) -> &'static str {
+        match self {
+            Rate::Quarter => "1/4",
+            Rate::Eighth => "1/8",
+            Rate::EighthTriplet => "1/8T",
+            Rate::Sixteenth => "1/16",
+        }
+    }
+
+    fn beats(self) -> f64 {
+        match self {
+            Rate::Quarter => 1.0,
+            Rate::Eighth => 0.5,
+            Rate::EighthTriplet => 1.0 / 3.0,
+            Rate::Sixteenth => 0.25,
+        }
+    }
+}
+
+pub struct Arpeggiator {
+    pattern: Pattern,
+    rate: Rate,
+    tempo: u16,
+    octaves: u16,
+    // speeds of the chord's notes, in the order the voicing left them
+    notes: Vec<f64>,
+    // one pass of the pattern over every octave
+    sequence: Vec<f64>,
+    position: usize,
+    next_step: Instant,
+    // xorshift state for the Random pattern
+    seed: u32,
+}
+
+impl Arpeggiator {
+    pub fn new(settings: &Settings) -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.subsec_nanos()).unwrap_or(0);
+        let mut arp = Self {
+            pattern: Pattern::Off,
+            rate: Rate::default(),
+            tempo: 120,
+            octaves: 1,
+            notes: Vec::new(),
+            sequence: Vec::new(),
+            position: 0,
+            next_step: Instant::now(),
+            // xorshift gets stuck on 0
+            seed: seed | 1,
+        };
+        arp.configure(settings);
+        arp
+    }
+
+    // Picks up changes from the settings menu, a chord that's already going
+    // carries on with the new pattern.
+    pub fn configure(&mut self, settings: &Settings) {
+        self.pattern = settings.arp_pattern;
+        self.rate = settings.arp_rate;
+        self.tempo = settings.tempo.clamp(TEMPO_MIN, TEMPO_MAX);
+        self.octaves = settings.arp_octaves.clamp(1, OCTAVES_MAX);
+        if self.pattern == Pattern::Off {
+            self.stop();
+        } else {
+            self.sequence = self.sequence();
+            self.position = 0;
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.pattern != Pattern::Off
+    }
+
+    // Starts arpeggiating a new chord, the first note plays straight away.
+    pub fn start(&mut self, notes: Vec<f64>) {
+        self.notes = notes;
+        self.sequence = self.sequence();
+        self.position = 0;
+        self.next_step = Instant::now();
+    }
+
+    pub fn stop(&mut self) {
+        self.notes.clear();
+        self.sequence.clear();
+    }
+
+    // Speed of the note to play if one is due. Steps stay on the grid unless
+    // the loop was held up for more than a step (e.g. by a fullscreen message).
+    pub fn step(&mut self) -> Option<f64> {
+        if self.sequence.is_empty() || Instant::now() < self.next_step {
+            return None;
+        }
+        let step_time = Duration::from_secs_f64(60.0 / self.tempo as f64 * self.rate.beats());
+        self.next_step += step_time;
+        if self.next_step < Instant::now() {
+            self.next_step = Instant::now() + step_time;
+        }
+        let idx = match self.pattern {
+            Pattern::Random => self.random() as usize,
+            _ => self.position,
+        };
+        let note = self.sequence[idx % self.sequence.len()];
+        self.position = (self.position + 1) % self.sequence.len();
+        Some(note)
+    }
+
+    // fits after the voicing on the bottom line of the screen
+    pub fn status(&self) -> String {
+        format!("{} {} {}bpm", self.pattern.abbrev(), self.rate.abbrev(), self.tempo)
+    }
+
+    fn sequence(&self) -> Vec<f64> {
+        let mut sequence: Vec<f64> = (0..self.octaves as i32)
+            .flat_map(|octave| self.notes.iter().map(move |note| note * 2.0_f64.powi(octave)))
+            .collect();
+        // sorted after adding the octaves, a 9th chord spans more than one, and
+        // notes the octaves doubled up are only played once
+        if self.pattern != Pattern::AsPlayed {
+            sequence.sort_by(|a, b| a.total_cmp(b));
+            sequence.dedup_by(|a, b| (*a / *b - 1.0).abs() < 0.01);
+        }
+        match self.pattern {
+            Pattern::Down => sequence.reverse(),
+            Pattern::UpDown if sequence.len() > 2 => {
+                let down: Vec<f64> = sequence[1..sequence.len() - 1].iter().rev().copied().collect();
+                sequence.extend(down);
+            }
+            _ => {}
+        }
+        sequence
+    }
+
+    fn random(&mut self) -> u32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+}
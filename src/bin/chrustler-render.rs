@@ -0,0 +1,17 @@
+// Renders a RON chord script to a WAV file without any audio or Pi hardware.
+use std::{env, path::Path, process::exit};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <script.ron> <out.wav>", args[0]);
+        exit(1);
+    }
+    match chrustler::render::render_file(Path::new(&args[1]), Path::new(&args[2])) {
+        Ok(_) => println!("wrote {}", args[2]),
+        Err(e) => {
+            eprintln!("render failed: {}", e);
+            exit(1);
+        }
+    }
+}
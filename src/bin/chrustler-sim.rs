@@ -0,0 +1,251 @@
+// Terminal simulator, plays the same chord engine as the device with the
+// keypad, encoders and ToF sensor mapped to the computer keyboard and the OLED
+// drawn with block characters.
+use std::{io::{self, Read, Write}, sync::{Arc, Mutex}, thread::{sleep, spawn}, time::{Duration, Instant}};
+
+use nix::sys::termios::{self, LocalFlags, SetArg, Termios};
+use chrustler::{display::{self, MockDisplay}, encoders::MockEncoder, keypad::{Keypad, MockKeypad}, tof::{self, MockTof}, SharedState};
+
+// Terminals only report key presses (and auto-repeats of them), so a key reads
+// as held until the repeat delay passes, then for as long as repeats keep coming.
+const KEY_REPEAT_DELAY: u64 = 500;
+const KEY_REPEAT_GAP: u64 = 100;
+const BUTTON_PRESS: u64 = 150;
+// long enough to count as a long press on the device
+const BUTTON_HOLD: u64 = 1200;
+const FRAME_TIME: u64 = 50;
+const TOF_STEP: u16 = 20;
+const TOF_MAX: u16 = 300;
+
+const HELP: [&str; 10] = [
+    "keys: 0-9 A-D * #   (letters are case insensitive)",
+    "up/down: volume encoder    tab: volume button (I/O)",
+    "left/right: key encoder    enter: key button (sample select)",
+    "s: long press key button (settings, volume encoder picks, key encoder sets)",
+    "v/V: push and turn key encoder (next/previous voicing)",
+    "m: latch # down (quality modifier, key encoder picks the quality)",
+    "l: latch D down (shift layer, borrowed chords and secondary dominants)",
+    "/ then 0-7 right after a chord key: press a bass key under it (slash chord)",
+    "[ ]: move hand closer/further from ToF    \\: remove hand",
+    "q: quit",
+];
+
+fn main() {
+    let keypad_in = MockKeypad::new();
+    let enc_a = MockEncoder::new();
+    let enc_b = MockEncoder::new();
+    let display = MockDisplay::new();
+    let tof_sensor = MockTof::new();
+    // without the codec this only keeps track of the settings
+    let shared = SharedState::new();
+    tof::poll_tof(Arc::new(Mutex::new(tof_sensor.clone())), shared.filter.clone(), shared.cur_hpf.clone(), shared.cur_lpf.clone(), shared.tof_enabled_low.clone(), shared.tof_enabled_high.clone());
+
+    let orig_termios = termios::tcgetattr(io::stdin()).expect("stdin is not a terminal!");
+    let mut raw_termios = orig_termios.clone();
+    raw_termios.local_flags.remove(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG);
+    termios::tcsetattr(io::stdin(), SetArg::TCSANOW, &raw_termios).expect("failed to set terminal mode");
+    let orig_termios = Arc::new(Mutex::new(orig_termios));
+
+    // put the terminal back even if the engine panics
+    let hook_termios = orig_termios.clone();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal(&hook_termios);
+        default_hook(info);
+    }));
+
+    print!("\x1b[2J\x1b[?25l");
+    let render_display = display.clone();
+    let hand = Arc::new(Mutex::new(None));
+    let render_hand = hand.clone();
+    spawn(move || render(render_display, render_hand));
+
+    let (input_keypad, input_enc_a, input_enc_b) = (keypad_in.clone(), enc_a.clone(), enc_b.clone());
+    spawn(move || read_input(input_keypad, input_enc_a, input_enc_b, tof_sensor, hand, orig_termios));
+
+    chrustler::run(keypad_in, enc_a, enc_b, display, shared);
+}
+
+fn restore_terminal(orig_termios: &Mutex<Termios>) {
+    if let Ok(orig) = orig_termios.lock() {
+        let _ = termios::tcsetattr(io::stdin(), SetArg::TCSANOW, &orig);
+    }
+    println!("\x1b[?25h");
+    let _ = io::stdout().flush();
+}
+
+fn read_input(keypad_in: MockKeypad, enc_a: MockEncoder, enc_b: MockEncoder, tof_sensor: MockTof, hand: Arc<Mutex<Option<u16>>>, orig_termios: Arc<Mutex<Termios>>) {
+    // (key, last time it was seen, whether the terminal has started repeating it)
+    let held: Arc<Mutex<Option<(Keypad, Instant, bool)>>> = Arc::new(Mutex::new(None));
+    let release_held = held.clone();
+    let release_keypad = keypad_in.clone();
+    spawn(move || loop {
+        sleep(Duration::from_millis(10));
+        let mut held = release_held.lock().expect("failed to lock held key");
+        if let Some((_, last_seen, repeating)) = *held {
+            let timeout = if repeating { KEY_REPEAT_GAP } else { KEY_REPEAT_DELAY };
+            if last_seen.elapsed() > Duration::from_millis(timeout) {
+                release_keypad.release();
+                *held = None;
+            }
+        }
+    });
+
+    let mut stdin = io::stdin();
+    let mut byte = [0u8; 1];
+    // terminals can't hold two keys at once, so # and D can be latched down instead
+    let mut pound_latched = false;
+    let mut shift_latched = false;
+    // the next number key goes under the one that's down instead of replacing it
+    let mut bass_next = false;
+    loop {
+        if stdin.read_exact(&mut byte).is_err() {
+            break;
+        }
+        let key = match byte[0] {
+            b'0' => Some(Keypad::ZERO),
+            b'1' => Some(Keypad::ONE),
+            b'2' => Some(Keypad::TWO),
+            b'3' => Some(Keypad::THREE),
+            b'4' => Some(Keypad::FOUR),
+            b'5' => Some(Keypad::FIVE),
+            b'6' => Some(Keypad::SIX),
+            b'7' => Some(Keypad::SEVEN),
+            b'8' => Some(Keypad::EIGHT),
+            b'9' => Some(Keypad::NINE),
+            b'a' | b'A' => Some(Keypad::A),
+            b'b' | b'B' => Some(Keypad::B),
+            b'c' | b'C' => Some(Keypad::C),
+            b'd' | b'D' => Some(Keypad::D),
+            b'*' => Some(Keypad::STAR),
+            b'#' => Some(Keypad::POUND),
+            _ => None
+        };
+        match key {
+            Some(key) if bass_next => {
+                bass_next = false;
+                press_under(keypad_in.clone(), key, BUTTON_PRESS);
+                continue;
+            }
+            Some(key) => {
+                let mut held = held.lock().expect("failed to lock held key");
+                *held = match *held {
+                    Some((held_key, _, _)) if held_key == key => Some((key, Instant::now(), true)),
+                    _ => {
+                        keypad_in.press(key);
+                        Some((key, Instant::now(), false))
+                    }
+                };
+                continue;
+            }
+            None => {}
+        }
+
+        match byte[0] {
+            // arrow keys come in as ESC [ A-D
+            0x1b => {
+                let mut seq = [0u8; 2];
+                if stdin.read_exact(&mut seq).is_err() {
+                    break;
+                }
+                match seq {
+                    [b'[', b'A'] => enc_a.turn(1),
+                    [b'[', b'B'] => enc_a.turn(-1),
+                    [b'[', b'C'] => enc_b.turn(1),
+                    [b'[', b'D'] => enc_b.turn(-1),
+                    _ => {}
+                }
+            }
+            b'\t' => press_button(enc_a.clone(), BUTTON_PRESS),
+            b'\n' | b'\r' => press_button(enc_b.clone(), BUTTON_PRESS),
+            b's' | b'S' => press_button(enc_b.clone(), BUTTON_HOLD),
+            b'm' | b'M' => {
+                pound_latched = !pound_latched;
+                keypad_in.set_held(Keypad::POUND, pound_latched);
+            }
+            b'l' | b'L' => {
+                shift_latched = !shift_latched;
+                keypad_in.set_held(Keypad::D, shift_latched);
+            }
+            b'/' => bass_next = true,
+            b'v' => push_turn(enc_b.clone(), 1),
+            b'V' => push_turn(enc_b.clone(), -1),
+            b'[' | b']' | b'\\' => {
+                let mut hand = hand.lock().expect("failed to lock hand distance");
+                *hand = match (byte[0], *hand) {
+                    (b'\\', _) => None,
+                    (b'[', None) => Some(TOF_MAX - TOF_STEP),
+                    (b'[', Some(distance)) => Some(distance.saturating_sub(TOF_STEP)),
+                    (_, None) => None,
+                    (_, Some(distance)) if distance + TOF_STEP > TOF_MAX => None,
+                    (_, Some(distance)) => Some(distance + TOF_STEP),
+                };
+                tof_sensor.set_distance(*hand);
+            }
+            // q or ctrl-c
+            b'q' | b'Q' | 0x03 => break,
+            _ => {}
+        }
+    }
+    restore_terminal(&orig_termios);
+    std::process::exit(0);
+}
+
+fn press_button(encoder: MockEncoder, length: u64) {
+    encoder.set_pressed(true);
+    spawn(move || {
+        sleep(Duration::from_millis(length));
+        encoder.set_pressed(false);
+    });
+}
+
+// Holds a key down underneath whichever one is pressed, like a second finger.
+fn press_under(keypad_in: MockKeypad, key: Keypad, length: u64) {
+    keypad_in.set_held(key, true);
+    spawn(move || {
+        sleep(Duration::from_millis(length));
+        keypad_in.set_held(key, false);
+    });
+}
+
+// Turns while the button is down, the press outlasts the turn like it would by hand.
+fn push_turn(encoder: MockEncoder, steps: i64) {
+    press_button(encoder.clone(), BUTTON_PRESS);
+    sleep(Duration::from_millis(FRAME_TIME));
+    encoder.turn(steps);
+}
+
+// Two pixel rows per line using half block characters, so the 128x64 screen is
+// 128 columns by 32 lines.
+fn render(display: MockDisplay, hand: Arc<Mutex<Option<u16>>>) {
+    loop {
+        let frame = display.frame();
+        let mut out = String::from("\x1b[H");
+        out.push_str(&format!("┌{}┐\x1b[K\n", "─".repeat(display::WIDTH)));
+        for y in (0..display::HEIGHT).step_by(2) {
+            out.push('│');
+            for (top, bottom) in frame[y].iter().zip(frame[y + 1].iter()) {
+                out.push(match (*top, *bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push_str("│\x1b[K\n");
+        }
+        out.push_str(&format!("└{}┘\x1b[K\n", "─".repeat(display::WIDTH)));
+        let hand_text = match *hand.lock().expect("failed to lock hand distance") {
+            Some(distance) => format!("hand: {}mm", distance),
+            None => "hand: none".to_string(),
+        };
+        out.push_str(&format!("{}\x1b[K\n", hand_text));
+        for line in HELP {
+            out.push_str(&format!("{}\x1b[K\n", line));
+        }
+        out.push_str("\x1b[J");
+        print!("{}", out);
+        let _ = io::stdout().flush();
+        sleep(Duration::from_millis(FRAME_TIME));
+    }
+}
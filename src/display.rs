@@ -0,0 +1,93 @@
+use std::{convert::Infallible, fmt::Debug, sync::{Arc, Mutex}};
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+#[cfg(feature = "pi")]
+use rppal::i2c::I2c;
+#[cfg(feature = "pi")]
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, Ssd1306};
+
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 64;
+
+pub type Frame = [[bool; WIDTH]; HEIGHT];
+
+// A buffered 128x64 monochrome screen, drawing only touches the buffer until
+// flush sends it to the panel.
+pub trait Screen: DrawTarget<Color = BinaryColor, Error: Debug> {
+    fn clear_buffer(&mut self);
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "pi")]
+pub type PiDisplay = Ssd1306<I2CInterface<I2c>, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>;
+
+#[cfg(feature = "pi")]
+impl Screen for PiDisplay {
+    fn clear_buffer(&mut self) {
+        Ssd1306::clear_buffer(self);
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ssd1306::flush(self)
+    }
+}
+
+// In-memory screen, flushed frames can be read back through a cloned handle.
+#[derive(Clone)]
+pub struct MockDisplay {
+    buffer: Frame,
+    frame: Arc<Mutex<Frame>>,
+}
+
+impl MockDisplay {
+    pub fn new() -> Self {
+        Self {
+            buffer: [[false; WIDTH]; HEIGHT],
+            frame: Arc::new(Mutex::new([[false; WIDTH]; HEIGHT])),
+        }
+    }
+
+    // last flushed frame, indexed [y][x]
+    pub fn frame(&self) -> Frame {
+        *self.frame.lock().expect("failed to lock mock display")
+    }
+}
+
+impl Default for MockDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for MockDisplay {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for MockDisplay {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 && (point.x as usize) < WIDTH && (point.y as usize) < HEIGHT {
+                self.buffer[point.y as usize][point.x as usize] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Screen for MockDisplay {
+    fn clear_buffer(&mut self) {
+        self.buffer = [[false; WIDTH]; HEIGHT];
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        *self.frame.lock().expect("failed to lock mock display") = self.buffer;
+        Ok(())
+    }
+}
@@ -1,17 +1,101 @@
-use rppal::{gpio::{Event, Gpio, InputPin}, i2c::I2c};
-use std::sync::{Arc, atomic::AtomicI64};
+#[cfg(feature = "pi")]
+use rppal::gpio::{Event, Gpio, InputPin, Trigger};
+#[cfg(feature = "pi")]
+use std::time::Duration;
+use std::sync::{Arc, atomic::{AtomicBool, AtomicI64}};
 
+#[cfg(feature = "pi")]
 pub const ENC_A_DT: u8 = 5;
+#[cfg(feature = "pi")]
 pub const ENC_A_CLK: u8 = 6;
+#[cfg(feature = "pi")]
 pub const ENC_A_PB: u8 = 16;
+#[cfg(feature = "pi")]
 pub const ENC_B_DT: u8 = 12;
+#[cfg(feature = "pi")]
 pub const ENC_B_CLK: u8 = 13;
+#[cfg(feature = "pi")]
 pub const ENC_B_PB: u8 = 26;
 
-pub fn encoder_pos(event: Event, dt_pin: &InputPin, counter: &AtomicI64) {
+// A rotary encoder with a push button, the count only ever goes up or down by
+// detents so callers compare it against the last value they saw.
+pub trait Encoder {
+    fn count(&self) -> i64;
+    fn is_pressed(&self) -> bool;
+}
+
+#[cfg(feature = "pi")]
+pub struct PiEncoder {
+    counter: Arc<AtomicI64>,
+    // the interrupt is dropped with the pin so it has to live as long as the encoder
+    _clk: InputPin,
+    pb: InputPin,
+}
+
+#[cfg(feature = "pi")]
+impl PiEncoder {
+    pub fn new(gpio: &Gpio, dt: u8, clk: u8, pb: u8) -> Self {
+        let dt_pin = gpio.get(dt).expect("couldn't get GPIO").into_input();
+        let mut clk_pin = gpio.get(clk).expect("couldn't get GPIO").into_input();
+        let pb_pin = gpio.get(pb).expect("couldn't get GPIO").into_input_pullup();
+        let counter = Arc::new(AtomicI64::new(0));
+        let counter_int = counter.clone();
+        let _ = clk_pin.set_async_interrupt(Trigger::FallingEdge, Some(Duration::from_millis(7)), move |e| encoder_pos(e, &dt_pin, &counter_int));
+        Self {
+            counter,
+            _clk: clk_pin,
+            pb: pb_pin,
+        }
+    }
+}
+
+#[cfg(feature = "pi")]
+impl Encoder for PiEncoder {
+    fn count(&self) -> i64 {
+        self.counter.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.pb.is_low()
+    }
+}
+
+#[cfg(feature = "pi")]
+pub fn encoder_pos(_event: Event, dt_pin: &InputPin, counter: &AtomicI64) {
     if dt_pin.is_high() {
         counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
     } else {
         counter.fetch_add(-1, std::sync::atomic::Ordering::SeqCst);
     }
-}
\ No newline at end of file
+}
+
+// In-memory encoder, clones share the same counter and button state.
+#[derive(Clone, Default)]
+pub struct MockEncoder {
+    counter: Arc<AtomicI64>,
+    pressed: Arc<AtomicBool>,
+}
+
+impl MockEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn turn(&self, detents: i64) {
+        self.counter.fetch_add(detents, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn set_pressed(&self, pressed: bool) {
+        self.pressed.store(pressed, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl Encoder for MockEncoder {
+    fn count(&self) -> i64 {
+        self.counter.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.pressed.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
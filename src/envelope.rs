@@ -0,0 +1,132 @@
+// Attack/decay/sustain/release amplitude envelope, wrapped around each voice
+// in the audio path so a gate fades the note out instead of cutting it (and
+// clicking). The release is started from the main loop through a shared flag,
+// which the audio thread sees on the very next sample.
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+
+use awedio::{NextSample, Sound};
+
+use crate::settings::Settings;
+
+// limits of the settings, in ms
+pub const ATTACK_MAX: u16 = 2000;
+pub const DECAY_MAX: u16 = 2000;
+pub const RELEASE_MAX: u16 = 4000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Adsr {
+    // seconds
+    pub attack: f64,
+    pub decay: f64,
+    // level held after the decay, 0-1
+    pub sustain: f64,
+    pub release: f64,
+}
+
+impl Adsr {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            attack: settings.attack.min(ATTACK_MAX) as f64 / 1000.0,
+            decay: settings.decay.min(DECAY_MAX) as f64 / 1000.0,
+            sustain: settings.sustain.min(100) as f64 / 100.0,
+            release: settings.release.min(RELEASE_MAX) as f64 / 1000.0,
+        }
+    }
+
+    // level t seconds after the note started, while it's still held
+    fn held_level(&self, t: f64) -> f64 {
+        if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (t - self.attack) / self.decay
+        } else {
+            self.sustain
+        }
+    }
+}
+
+pub struct Envelope<S: Sound> {
+    inner: S,
+    adsr: Adsr,
+    released: Arc<AtomicBool>,
+    // seconds per frame, from the inner sound's rate so it's real time even
+    // after a speed change
+    frame_time: f64,
+    channel: u16,
+    time: f64,
+    level: f64,
+    // level and time the release started at
+    release_start: Option<(f64, f64)>,
+}
+
+impl<S: Sound> Envelope<S> {
+    // released starts the release once it's set, it can be shared with the
+    // inner sound (e.g. to stop a sustain loop at the same time)
+    pub fn new(inner: S, adsr: Adsr, released: Arc<AtomicBool>) -> Self {
+        let frame_time = 1.0 / inner.sample_rate().max(1) as f64;
+        Self {
+            inner,
+            adsr,
+            released,
+            frame_time,
+            channel: 0,
+            time: 0.0,
+            level: 0.0,
+            release_start: None,
+        }
+    }
+
+    // Moves on a frame, false once the release has finished.
+    fn advance(&mut self) -> bool {
+        if self.release_start.is_none() && self.released.load(Ordering::SeqCst) {
+            self.release_start = Some((self.level, self.time));
+        }
+        self.level = match self.release_start {
+            Some((start_level, start_time)) => {
+                if self.adsr.release <= 0.0 {
+                    0.0
+                } else {
+                    start_level * (1.0 - (self.time - start_time) / self.adsr.release)
+                }
+            }
+            None => self.adsr.held_level(self.time),
+        };
+        self.time += self.frame_time;
+        !(self.release_start.is_some() && self.level <= 0.0)
+    }
+}
+
+impl<S: Sound> Sound for Envelope<S> {
+    fn channel_count(&self) -> u16 {
+        self.inner.channel_count()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn next_sample(&mut self) -> Result<NextSample, awedio::Error> {
+        match self.inner.next_sample()? {
+            NextSample::Sample(s) => {
+                if self.channel == 0 && !self.advance() {
+                    return Ok(NextSample::Finished);
+                }
+                self.channel = (self.channel + 1) % self.inner.channel_count().max(1);
+                Ok(NextSample::Sample((s as f64 * self.level) as i16))
+            }
+            NextSample::MetadataChanged => {
+                self.frame_time = 1.0 / self.inner.sample_rate().max(1) as f64;
+                self.channel = 0;
+                Ok(NextSample::MetadataChanged)
+            }
+            other => {
+                self.channel = 0;
+                Ok(other)
+            }
+        }
+    }
+
+    fn on_start_of_batch(&mut self) {
+        self.inner.on_start_of_batch();
+    }
+}
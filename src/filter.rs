@@ -0,0 +1,205 @@
+// Resonant low/high-pass filter in software, the chords are mixed into one
+// sound that is run through a high-pass then a low-pass biquad before it goes
+// to the sound card. The ToF handler sets the cutoffs from another thread.
+use std::{f32::consts::PI, sync::{Arc, atomic::AtomicU32}};
+
+use awedio::{NextSample, Sound};
+
+// fully open, far enough out that the filter doesn't colour the sound
+pub const LPF_OPEN: f32 = 20000.0;
+pub const HPF_OPEN: f32 = 20.0;
+// where the cutoff ends up with a hand right on the sensor
+pub const LPF_CLOSED: f32 = 200.0;
+pub const HPF_CLOSED: f32 = 2000.0;
+// Q of both stages, 0.707 would be flat
+pub const DEFAULT_RESONANCE: f32 = 2.0;
+// limits of the setting, below 0.5 the cutoff gets vague and above 10 it whistles
+pub const RESONANCE_MIN: f32 = 0.5;
+pub const RESONANCE_MAX: f32 = 10.0;
+// fraction of the way to the new cutoff moved every batch, smooths out the
+// steps between sensor readings
+const GLIDE: f32 = 0.5;
+
+// Cutoffs and resonance shared between the filter and whoever moves it, stored
+// as f32 bits so they can be set without a lock.
+pub struct FilterParams {
+    lpf_cutoff: AtomicU32,
+    hpf_cutoff: AtomicU32,
+    resonance: AtomicU32,
+}
+
+impl FilterParams {
+    pub fn new() -> Self {
+        Self {
+            lpf_cutoff: AtomicU32::new(LPF_OPEN.to_bits()),
+            hpf_cutoff: AtomicU32::new(HPF_OPEN.to_bits()),
+            resonance: AtomicU32::new(DEFAULT_RESONANCE.to_bits()),
+        }
+    }
+
+    pub fn set_lpf(&self, cutoff: f32) {
+        self.lpf_cutoff.store(cutoff.to_bits(), std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn set_hpf(&self, cutoff: f32) {
+        self.hpf_cutoff.store(cutoff.to_bits(), std::sync::atomic::Ordering::SeqCst);
+    }
+
+    // kept in range, it can come from a hand edited settings.ron
+    pub fn set_resonance(&self, q: f32) {
+        self.resonance.store(q.clamp(RESONANCE_MIN, RESONANCE_MAX).to_bits(), std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn lpf(&self) -> f32 {
+        f32::from_bits(self.lpf_cutoff.load(std::sync::atomic::Ordering::SeqCst))
+    }
+
+    pub fn hpf(&self) -> f32 {
+        f32::from_bits(self.hpf_cutoff.load(std::sync::atomic::Ordering::SeqCst))
+    }
+
+    pub fn resonance(&self) -> f32 {
+        f32::from_bits(self.resonance.load(std::sync::atomic::Ordering::SeqCst))
+    }
+}
+
+impl Default for FilterParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Cutoff for a 0.0 (closed) to 1.0 (open) position, exponential so equal hand
+// movements sound like equal changes in pitch.
+pub fn sweep(closed: f32, open: f32, position: f32) -> f32 {
+    closed * (open / closed).powf(position.clamp(0.0, 1.0))
+}
+
+#[derive(Clone, Copy)]
+enum Pass {
+    Low,
+    High,
+}
+
+// Direct form I biquad, coefficients from the RBJ audio EQ cookbook.
+#[derive(Default)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    fn set(&mut self, pass: Pass, cutoff: f32, q: f32, sample_rate: f32) {
+        // keep away from nyquist or the filter blows up
+        let cutoff = cutoff.clamp(10.0, sample_rate * 0.45);
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let alpha = w0.sin() / (2.0 * q.max(0.1));
+        let cos_w0 = w0.cos();
+        let a0 = 1.0 + alpha;
+        let (b0, b1, b2) = match pass {
+            Pass::Low => ((1.0 - cos_w0) / 2.0, 1.0 - cos_w0, (1.0 - cos_w0) / 2.0),
+            Pass::High => ((1.0 + cos_w0) / 2.0, -(1.0 + cos_w0), (1.0 + cos_w0) / 2.0),
+        };
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = (-2.0 * cos_w0) / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+// Wraps a mono sound. It never pauses or finishes on its own, silence from the
+// inner sound is still run through so the resonance rings out, and it keeps the
+// mixer it is played on from ever running dry.
+pub struct Filter<S: Sound> {
+    inner: S,
+    params: Arc<FilterParams>,
+    lpf: Biquad,
+    hpf: Biquad,
+    lpf_cutoff: f32,
+    hpf_cutoff: f32,
+    inner_paused: bool,
+}
+
+impl<S: Sound> Filter<S> {
+    pub fn new(inner: S, params: Arc<FilterParams>) -> Self {
+        let mut filter = Self {
+            inner,
+            lpf_cutoff: params.lpf(),
+            hpf_cutoff: params.hpf(),
+            params,
+            lpf: Biquad::default(),
+            hpf: Biquad::default(),
+            inner_paused: false,
+        };
+        filter.update_coefficients();
+        filter
+    }
+
+    fn update_coefficients(&mut self) {
+        let sample_rate = self.inner.sample_rate() as f32;
+        let q = self.params.resonance();
+        self.lpf.set(Pass::Low, self.lpf_cutoff, q, sample_rate);
+        self.hpf.set(Pass::High, self.hpf_cutoff, q, sample_rate);
+    }
+
+    fn process(&mut self, sample: i16) -> i16 {
+        let x = sample as f32;
+        let y = self.lpf.process(self.hpf.process(x));
+        y.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+}
+
+impl<S: Sound> Sound for Filter<S> {
+    fn channel_count(&self) -> u16 {
+        self.inner.channel_count()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn on_start_of_batch(&mut self) {
+        self.inner_paused = false;
+        self.inner.on_start_of_batch();
+        // glide in log frequency so the sweep speed is even across the range
+        let lpf_target = self.params.lpf();
+        let hpf_target = self.params.hpf();
+        self.lpf_cutoff *= (lpf_target / self.lpf_cutoff).powf(GLIDE);
+        self.hpf_cutoff *= (hpf_target / self.hpf_cutoff).powf(GLIDE);
+        self.update_coefficients();
+    }
+
+    fn next_sample(&mut self) -> Result<NextSample, awedio::Error> {
+        if self.inner_paused {
+            return Ok(NextSample::Sample(self.process(0)));
+        }
+        match self.inner.next_sample()? {
+            NextSample::Sample(s) => Ok(NextSample::Sample(self.process(s))),
+            NextSample::MetadataChanged => {
+                self.update_coefficients();
+                Ok(NextSample::MetadataChanged)
+            }
+            NextSample::Paused | NextSample::Finished => {
+                self.inner_paused = true;
+                Ok(NextSample::Sample(self.process(0)))
+            }
+        }
+    }
+}
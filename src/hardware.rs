@@ -0,0 +1,135 @@
+// Device setup, with the `pi` feature this talks to the real GPIO/I2C
+// peripherals, otherwise every device is an in-memory mock so the rest of the
+// program can run on any machine.
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU16}};
+
+use crate::display::Screen;
+use crate::filter::FilterParams;
+use crate::tof;
+
+#[cfg(feature = "pi")]
+use std::{fs::File, io::Read};
+#[cfg(feature = "pi")]
+use mcp23017::MCP23017;
+#[cfg(feature = "pi")]
+use rppal::{gpio::{Gpio, InputPin, Trigger}, i2c::I2c};
+#[cfg(feature = "pi")]
+use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
+#[cfg(feature = "pi")]
+use vl53l1x::Vl53l1x;
+#[cfg(feature = "pi")]
+use crate::{calibration::{self, CalibrationDataRem}, display::PiDisplay, encoders::{self, PiEncoder}, fullscreen_msg, keypad};
+
+#[cfg(not(feature = "pi"))]
+use crate::{display::MockDisplay, encoders::MockEncoder, keypad::MockKeypad};
+
+#[cfg(feature = "pi")]
+const TOF_INT_PIN: u8 = 17;
+
+#[cfg(feature = "pi")]
+pub fn init_keypad() -> MCP23017<I2c> {
+    let i2c = rppal::i2c::I2c::new().expect("failed to open I2C bus!");
+    keypad::init_keypad(i2c).expect("failed to initialize GPIO expander")
+}
+
+#[cfg(feature = "pi")]
+pub fn init_display() -> PiDisplay {
+    // using an alternate address: https://docs.rs/ssd1306/latest/ssd1306/struct.I2CDisplayInterface.html
+    let i2c = rppal::i2c::I2c::new().expect("failed to open I2C bus!");
+    let interface = I2CDisplayInterface::new_custom_address(i2c, 0x3C);
+    let mut display = Ssd1306::new(
+        interface,
+        DisplaySize128x64,
+        DisplayRotation::Rotate0,
+    ).into_buffered_graphics_mode();
+    display.init().unwrap();
+    display
+}
+
+#[cfg(feature = "pi")]
+pub fn init_encoders() -> (PiEncoder, PiEncoder) {
+    let gpio = Gpio::new().expect("failed to init gpio");
+    let enc_a = PiEncoder::new(&gpio, encoders::ENC_A_DT, encoders::ENC_A_CLK, encoders::ENC_A_PB);
+    let enc_b = PiEncoder::new(&gpio, encoders::ENC_B_DT, encoders::ENC_B_CLK, encoders::ENC_B_PB);
+    (enc_a, enc_b)
+}
+
+// Loads (or runs) the sensor calibration and starts ranging. The returned pin
+// holds the interrupt so it must be kept alive, None means the program should
+// exit after a calibration run.
+#[cfg(feature = "pi")]
+pub fn init_tof<D: Screen>(display: &mut D, filter: Arc<FilterParams>, cur_hpf: Arc<AtomicU16>, cur_lpf: Arc<AtomicU16>, enabled_low: Arc<AtomicBool>, enabled_high: Arc<AtomicBool>) -> Option<InputPin> {
+    let tof_sensor: Arc<Mutex<Vl53l1x>> = Arc::new(Mutex::new(tof::init_tof()));
+    let thr_sens = tof_sensor.clone();
+    let main_thr_sens = tof_sensor.clone();
+
+    let mut calibration_lock = main_thr_sens.lock().expect("failed to get TOF lock for calibration");
+    let _ = match File::open("calibration.ron") {
+        Ok(mut calibration_file) => {
+            let mut calibration_string= String::new();
+            match calibration_file.read_to_string(&mut calibration_string) {
+                Ok(_) => {}
+                Err(_) => {
+                    drop(calibration_lock);
+                    fullscreen_msg(display, "TOF Calibration".to_string());
+                    calibration::calibration(main_thr_sens.clone());
+                    return None
+                }
+            }
+
+            let mut de = ron::Deserializer::from_str(&calibration_string).expect("failed to deserialize!");
+            match CalibrationDataRem::deserialize(&mut de) {
+                Ok(mut calibration_data) => {
+                    // let data_wrap:  CalibrationDataRem = calibration_data;
+                    // let mut calibration_data: CalibrationData = data_wrap.into();
+                    calibration_lock.set_calibration_data(&mut calibration_data);
+                    drop(calibration_lock);
+                }
+                Err(_) => {
+                    drop(calibration_lock);
+                    fullscreen_msg(display, "TOF Calibration".to_string());
+                    calibration::calibration(main_thr_sens.clone());
+                }
+            }
+        }
+
+        Err(_) => {
+            drop(calibration_lock);
+            fullscreen_msg(display, "TOF Calibration".to_string());
+            calibration::calibration(main_thr_sens.clone());
+        }
+    };
+
+    let cur_roi: tof::ROIRight = tof::ROIRight::new(true);
+    let gpio = Gpio::new().expect("failed to init gpio");
+    let mut tof_int_pin = gpio.get(TOF_INT_PIN).expect("failed to get tof interrupt pin").into_input();
+    tof_int_pin.set_async_interrupt(Trigger::FallingEdge, None, move |_e| tof::tof_eq_int(thr_sens.clone(), &filter, &cur_roi, cur_hpf.clone(), cur_lpf.clone(), &enabled_low, &enabled_high)).expect("failed to setup TOF interrupt");
+    let mut sensor = main_thr_sens.lock().expect("failed to lock sensor to begin ranging");
+    sensor.start_ranging(vl53l1x::DistanceMode::Short).expect("failed to begin tof ranging");
+    drop(sensor);
+    Some(tof_int_pin)
+}
+
+#[cfg(not(feature = "pi"))]
+pub fn init_keypad() -> MockKeypad {
+    MockKeypad::new()
+}
+
+#[cfg(not(feature = "pi"))]
+pub fn init_display() -> MockDisplay {
+    MockDisplay::new()
+}
+
+#[cfg(not(feature = "pi"))]
+pub fn init_encoders() -> (MockEncoder, MockEncoder) {
+    (MockEncoder::new(), MockEncoder::new())
+}
+
+// There is no interrupt line without the sensor so the mock is polled instead,
+// the returned handle sets the distance it reads.
+#[cfg(not(feature = "pi"))]
+pub fn init_tof<D: Screen>(_display: &mut D, filter: Arc<FilterParams>, cur_hpf: Arc<AtomicU16>, cur_lpf: Arc<AtomicU16>, enabled_low: Arc<AtomicBool>, enabled_high: Arc<AtomicBool>) -> Option<tof::MockTof> {
+    let mock_tof = tof::MockTof::new();
+    tof::poll_tof(Arc::new(Mutex::new(mock_tof.clone())), filter, cur_hpf, cur_lpf, enabled_low, enabled_high);
+    Some(mock_tof)
+}
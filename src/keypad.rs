@@ -1,10 +1,9 @@
-use std::error::Error;
-use std::sync::{Arc, atomic::AtomicI64};
-use std::thread::sleep;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
 
+#[cfg(feature = "pi")]
 use mcp23017::MCP23017;
-use rppal::{gpio::{Event, Gpio, InputPin}, i2c::I2c};
+#[cfg(feature = "pi")]
+use rppal::i2c::I2c;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Keypad {
@@ -29,6 +28,83 @@ pub enum Keypad {
     IO,
 }
 
+// Anything that can be scanned for the currently pressed key.
+pub trait KeypadInput {
+    fn get_keypad(&mut self, last_input: Option<Keypad>) -> Option<Keypad>;
+    // whether one key is down, even if get_keypad is reporting another
+    fn is_held(&mut self, key: Keypad) -> bool;
+    // every key that is down
+    fn held_keys(&mut self) -> Vec<Keypad>;
+}
+
+#[cfg(feature = "pi")]
+impl KeypadInput for MCP23017<I2c> {
+    fn get_keypad(&mut self, last_input: Option<Keypad>) -> Option<Keypad> {
+        get_keypad(self, last_input)
+    }
+
+    fn is_held(&mut self, key: Keypad) -> bool {
+        is_held(self, key)
+    }
+
+    fn held_keys(&mut self) -> Vec<Keypad> {
+        held_keys(self)
+    }
+}
+
+// In-memory keypad, clones share the same pressed key so one handle can be
+// driven from a test or another thread while the main loop scans the other.
+#[derive(Clone, Default)]
+pub struct MockKeypad {
+    pressed: Arc<Mutex<Option<Keypad>>>,
+    // keys held down underneath the pressed one, e.g. a modifier
+    held: Arc<Mutex<Vec<Keypad>>>,
+}
+
+impl MockKeypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&self, key: Keypad) {
+        *self.pressed.lock().expect("failed to lock mock keypad") = Some(key);
+    }
+
+    pub fn release(&self) {
+        *self.pressed.lock().expect("failed to lock mock keypad") = None;
+    }
+
+    pub fn set_held(&self, key: Keypad, held: bool) {
+        let mut held_keys = self.held.lock().expect("failed to lock mock keypad");
+        held_keys.retain(|held_key| *held_key != key);
+        if held {
+            held_keys.push(key);
+        }
+    }
+}
+
+impl KeypadInput for MockKeypad {
+    fn get_keypad(&mut self, _last_input: Option<Keypad>) -> Option<Keypad> {
+        let pressed = *self.pressed.lock().expect("failed to lock mock keypad");
+        pressed.or_else(|| self.held.lock().expect("failed to lock mock keypad").last().copied())
+    }
+
+    fn is_held(&mut self, key: Keypad) -> bool {
+        *self.pressed.lock().expect("failed to lock mock keypad") == Some(key)
+            || self.held.lock().expect("failed to lock mock keypad").contains(&key)
+    }
+
+    fn held_keys(&mut self) -> Vec<Keypad> {
+        let mut held_keys = self.held.lock().expect("failed to lock mock keypad").clone();
+        if let Some(pressed) = *self.pressed.lock().expect("failed to lock mock keypad")
+            && !held_keys.contains(&pressed) {
+            held_keys.push(pressed);
+        }
+        held_keys
+    }
+}
+
+#[cfg(feature = "pi")]
 pub fn init_keypad(i2c: I2c) -> Option<MCP23017<I2c>> {
     let mut ex_gpio = MCP23017::new(i2c, 0x27).expect("failed to initialize GPIO expander");
     ex_gpio.init_hardware();
@@ -63,6 +139,7 @@ pub fn init_keypad(i2c: I2c) -> Option<MCP23017<I2c>> {
     Some(ex_gpio)
 }
 
+#[cfg(feature = "pi")]
 pub fn get_keypad(ex_gpio: &mut MCP23017<I2c>, last_input: Option<Keypad>) -> Option<Keypad>{
     let mut out = None;
     for row in 0..4 {
@@ -73,6 +150,8 @@ pub fn get_keypad(ex_gpio: &mut MCP23017<I2c>, last_input: Option<Keypad>) -> Op
                 //println!("row {}, col {}", row, col);
                 out = get_keycode(row, col);
                 if out == last_input {
+                    // leave the row high again or it ghosts into later reads
+                    let _ = ex_gpio.digital_write(row + 8, true);
                     return out;
                 }
             }
@@ -82,6 +161,40 @@ pub fn get_keypad(ex_gpio: &mut MCP23017<I2c>, last_input: Option<Keypad>) -> Op
     out
 }
 
+// Drives just the key's row so it can be read while another key is down.
+#[cfg(feature = "pi")]
+pub fn is_held(ex_gpio: &mut MCP23017<I2c>, key: Keypad) -> bool {
+    for row in 0..4 {
+        for col in 4..8 {
+            if get_keycode(row, col) == Some(key) {
+                let _ = ex_gpio.digital_write(row + 8, false);
+                let held = ex_gpio.digital_read(col + 8).unwrap_or(false);
+                let _ = ex_gpio.digital_write(row + 8, true);
+                return held;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(feature = "pi")]
+pub fn held_keys(ex_gpio: &mut MCP23017<I2c>) -> Vec<Keypad> {
+    let mut held = Vec::new();
+    for row in 0..4 {
+        let _ = ex_gpio.digital_write(row + 8, false);
+        for col in 4..8 {
+            if ex_gpio.digital_read(col + 8).unwrap_or(false) {
+                if let Some(key) = get_keycode(row, col) {
+                    held.push(key);
+                }
+            }
+        }
+        let _ = ex_gpio.digital_write(row + 8, true);
+    }
+    held
+}
+
+#[cfg(feature = "pi")]
 pub fn get_keycode(row: u8, column: u8) -> Option<Keypad> {
     let mut out = None;
     match row {
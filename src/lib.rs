@@ -0,0 +1,1381 @@
+use awedio::{backends::{CpalBackend, CpalBufferSize}, manager::{BackendSource, Manager}, sounds::wrappers::Pausable, *};
+use nix::libc::major;
+use core::num;
+use std::{env, fmt::format, fs, path, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU16}}, thread::{current, sleep}, time::{Duration, Instant}};
+use embedded_graphics::{
+    mono_font::{MonoTextStyleBuilder, ascii::{FONT_6X10, FONT_8X13}},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Baseline, Text},
+};
+use num_traits::pow::Pow;
+use serde::Deserialize;
+
+// use crate::keypad::*;
+// use crate::encoders::*;
+// use crate::tof::*;
+pub mod keypad;
+pub mod encoders;
+pub mod tof;
+pub mod display;
+pub mod hardware;
+pub mod render;
+pub mod mixer;
+pub mod filter;
+pub mod settings;
+pub mod tuning;
+pub mod scale;
+pub mod arp;
+pub mod strum;
+pub mod voice;
+pub mod envelope;
+pub mod sample;
+pub mod pitch;
+#[cfg(feature = "pi")]
+mod calibration;
+use keypad::KeypadInput;
+use encoders::Encoder;
+use display::Screen;
+use mixer::Mixer;
+use filter::{Filter, FilterParams};
+use settings::Settings;
+use tuning::Tuning;
+use scale::{Chord, Mode, Quality, Voicing};
+use arp::Arpeggiator;
+use strum::Strum;
+use voice::VoiceManager;
+use envelope::Adsr;
+use sample::{Sample, SampleMeta};
+
+
+
+const SAMPLE_RATE: usize = 48000;
+const SIZE: usize = 1024;
+const PADDING: usize = SIZE / 2;
+const POWER_THRESHOLD: f64 = 0.0001;
+const CLARITY_THRESHOLD: f64 = 0.25;
+
+const TEMP_PIN: u8 = 27;
+
+pub const DEFAULT_EQ_LEVEL: u16 = 12;
+const VOL_LUT: [u16; 76] = [0,5,9,13,17,20,24,26,29,32,34,37,39,41,43,45,47,48,50,52,53,55,56,58,59,60,61,63,64,65,66,67,68,70,71,72,73,74,75,75,76,77,78,79,80,81,81,82,83,84,85,85,86,87,87,88,89,89,90,91,91,92,93,93,94,94,95,96,96,97,97,98,98,99,99,100];
+
+const TRIADS: u16 = 3;
+const SEVENTHS: u16 = 4;
+const NINTHS: u16 = 5;
+
+const INPUT_TIMEOUT: u64 = 150;
+const FULLSCREEN_TIMEOUT: u64 = 75;
+const LONG_PRESS: u64 = 1000;
+// the sample edit screen moves the root up to this many cents either way
+const ROOT_RANGE: i64 = 2400;
+const REFERENCE_VOLUME: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+enum Key {
+    C,
+    Cs,
+    D,
+    Ds,
+    E,
+    F,
+    Fs,
+    G,
+    Gs,
+    A,
+    As,
+    B
+}
+
+const KEYS: [Key; 12] = [Key::C, Key::Cs, Key::D, Key::Ds, Key::E, Key::F, Key::Fs, Key::G, Key::Gs, Key::A, Key::As, Key::B];
+
+impl Key {
+    // semitones above C
+    fn semitone(self) -> i32 {
+        self as i32
+    }
+
+    // Equal temperament, in the octave starting at middle C.
+    fn frequency(self, a4: f64) -> f64 {
+        a4 * 2.0_f64.powf((self.semitone() - Key::A.semitone()) as f64 / 12.0)
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+enum Octave {
+    LOW,
+    MID,
+    HIGH
+}
+impl Octave {
+    fn multiplier(self) -> f64 {
+        match self {
+            Octave::LOW => 0.5,
+            Octave::MID => 1.0,
+            Octave::HIGH => 2.0
+        }
+    }
+}
+
+// Playback speed that moves a sample recorded at freq to the root of key in octave.
+fn octave_correction(key: Key, octave: Octave, freq: f64, a4: f64) -> f64 {
+    (key.frequency(a4) * octave.multiplier()) / freq
+}
+
+// The sound card's mixer, the filter and the ToF state, shared between the main
+// loop and the ToF sensor's thread.
+pub struct SharedState {
+    pub mixer: Arc<Mutex<Mixer>>,
+    pub filter: Arc<FilterParams>,
+    pub cur_hpf: Arc<AtomicU16>,
+    pub cur_lpf: Arc<AtomicU16>,
+    pub tof_enabled_low: Arc<AtomicBool>,
+    pub tof_enabled_high: Arc<AtomicBool>,
+}
+
+impl SharedState {
+    pub fn new() -> Self {
+        Self {
+            mixer: Arc::new(Mutex::new(Mixer::new())),
+            filter: Arc::new(FilterParams::new()),
+            cur_hpf: Arc::new(AtomicU16::new(DEFAULT_EQ_LEVEL)),
+            cur_lpf: Arc::new(AtomicU16::new(DEFAULT_EQ_LEVEL)),
+            tof_enabled_low: Arc::new(AtomicBool::new(true)),
+            tof_enabled_high: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+impl Default for SharedState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The keypad, encoders and screen, lent to the screens that take over from the
+// main loop for a while.
+struct Panel<'a, K: KeypadInput, E: Encoder, D: Screen> {
+    keypad_in: &'a mut K,
+    enc_a: &'a E,
+    enc_b: &'a E,
+    display: &'a mut D,
+}
+
+// Where those screens play notes, the same as the main loop's.
+struct Playback<'a> {
+    manager: &'a mut Manager,
+    voices: &'a mut VoiceManager,
+}
+
+// Runs the instrument on whichever devices it is handed, this only returns if
+// the program should exit.
+pub fn run<K: KeypadInput, E: Encoder, D: Screen>(mut keypad_in: K, enc_a: E, enc_b: E, mut display: D, shared: SharedState) {
+    // Setup
+    let mut settings = Settings::load();
+    shared.filter.set_resonance(settings.resonance);
+    let mut volume: i64 = 75;
+    set_volume(&shared.mixer, volume);
+    
+    let mut int_io = true;
+
+    set_io(false, &mut display, &shared.mixer);
+        // init display, set message
+
+        // init filters
+    tof::init_eq(&shared.mixer);
+
+    let mut last_counter_a: i64 = 0;
+    let mut last_counter_b: i64 = 0;
+
+        // TODO: Search for sample file names
+        // automatically select sound_xx where xx is the largest integer found there, and record xx + 1 as the next sample name
+
+    // Setup audio backend
+    let (mut backend, mut manager) = start_audio(&shared.filter);
+
+    let mut next_sample_no: usize = 0;
+    let mut sample_paths: Vec<String> = Vec::new();
+
+    // If the usb drive is plugged in use that, if not default to CWD
+    // (a dev machine might not have anything mounted under '/media' at all)
+    let mut media_path_entry = match find_usb_media() {
+        Some(usb_media_path) => usb_media_path,
+        None => {
+            env::current_dir().expect("No current working dir!")
+        }
+    };
+    
+    println!("using {:#?}", media_path_entry);
+    let user_media_dir = match fs::read_dir(media_path_entry.clone()) { // list user drive files
+         Ok(dir) => dir,
+         Err(_) => {
+            println!("using {:#?}", media_path_entry);
+            media_path_entry = env::current_dir().expect("No current working dir!");
+            fs::read_dir(media_path_entry.clone()).expect("failed to read CWD!")
+         }
+    };
+    let media_path = media_path_entry.to_str().unwrap().to_string();
+    
+    for entry_res in user_media_dir {
+        match entry_res {
+            Ok(entry) => {
+                if entry.path().extension().is_some() {
+                    if entry.path().extension().unwrap().to_str().is_some() {
+                        if entry.path().extension().unwrap().to_str().unwrap().eq("wav") {
+                            let this_path = entry.path().to_str().unwrap().to_string();
+                            sample_paths.push(entry.path().to_str().unwrap().to_string());
+                            let after_slash = this_path.rfind("/").unwrap() + 1;
+                            let dot = this_path.find(".").unwrap();
+                            if this_path[after_slash..after_slash + 6].eq("sound_") {
+                                println!("found '{}'", this_path);
+                                match this_path[after_slash + 6..dot].parse() {
+                                    Ok(sampleno) => {
+                                        if sampleno > next_sample_no {
+                                            next_sample_no = sampleno;
+                                        }
+                                    }
+                                    Err(_) => {}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    next_sample_no = next_sample_no + 1;
+
+    let mut current_sample_idx: usize = 0;
+    let init_smpl_path = if sample_paths.len() > 0 {
+        sample_paths[current_sample_idx].clone()
+    } else {
+        "test_arec.wav".to_string()
+    };
+
+    let init_sound = sample::open_wav(path::Path::new(&init_smpl_path)).expect("couldn't open audio file");
+
+    let mut sound = Sample::new(init_sound, SampleMeta::load(path::Path::new(&init_smpl_path)));
+    let mut current_freq: f64 = sample_root(&mut sound, &init_smpl_path, &pitch::DetectParams::from_settings(&settings), &mut display).expect("no pitch in the first sample!");
+
+    let scala_files = find_scala_files(&media_path);
+    let mut tuning = load_tuning(&settings, &media_path, &mut display);
+
+    let mut key =  Key::C;
+    let mut key_idx = 0;
+    //let correction: f64 = key.frequency() / (current_freq as f64);
+    // the built in modes then any from settings.ron, A cycles through them
+    let modes = scale::modes(&settings.modes);
+    let mut mode_idx: usize = 0;
+    let mut chord_type = TRIADS;
+    // applied to chords played with # held, the key encoder changes it while # is down
+    let mut quality = Quality::default();
+    // # was used as a modifier since it went down, so letting go isn't a hold
+    let mut modifier_used = false;
+    // D is the shift layer while it's held and only changes the chord type when
+    // it's tapped on its own
+    let mut shift_pressed = false;
+    let mut shift_used = false;
+    // degree of a second number key pressed while a chord's key is held, played
+    // under the chord as a slash chord
+    let mut bass: Option<u16> = None;
+        // init scale and mode to C major
+        // init chords to triads
+    let mut current_octave = Octave::MID;
+    // voicing, gate, arpeggiator and the voices every note is played on
+    let mut perf = Performance::new(&settings);
+
+    let mut hold = false;
+    let mut last_input: Option<keypad::Keypad> = None;
+    loop {
+        let quality_held = keypad_in.is_held(keypad::Keypad::POUND);
+        let held_quality = if quality_held {
+            Some(quality)
+        } else {
+            modifier_used = false;
+            None
+        };
+        let shift_held = keypad_in.is_held(keypad::Keypad::D);
+        if shift_pressed && !shift_held {
+            if !shift_used {
+                perf.voices.release_all();
+                chord_type = match chord_type {
+                    TRIADS => SEVENTHS,
+                    SEVENTHS => NINTHS,
+                    _ => TRIADS,
+                };
+            }
+            shift_pressed = false;
+            shift_used = false;
+        }
+        // STAR with D held - correct the sample's root. The keypad scan finds D
+        // before STAR so it's checked here rather than in the match below
+        if shift_held && keypad_in.is_held(keypad::Keypad::STAR) {
+            perf.arp.stop();
+            let smpl_path = sample_paths.get(current_sample_idx).cloned().unwrap_or(init_smpl_path.clone());
+            current_freq = sample_edit(&mut sound, current_freq, &smpl_path, tuning.a4, &mut Playback { manager: &mut manager, voices: &mut perf.voices }, &mut Panel { keypad_in: &mut keypad_in, enc_a: &enc_a, enc_b: &enc_b, display: &mut display });
+            // the encoders were used by the screen, don't apply their turns again
+            last_counter_a = enc_a.count();
+            last_counter_b = enc_b.count();
+            shift_used = true;
+            // STAR on its own starts recording, wait for it to be let go
+            while keypad_in.is_held(keypad::Keypad::STAR) {
+                sleep(Duration::from_millis(10));
+            }
+            last_input = None;
+            continue;
+        }
+
+        // a new bass key under the chord's key plays the chord again over it, e.g.
+        // 1 then 3 for C/E
+        let mut replay = false;
+        let chord_held = match last_input {
+            Some(keypad::Keypad::ZERO) => shift_held,
+            Some(input) => key_degree(input).is_some() || (shift_held && shift_chord(input).is_some()),
+            None => false,
+        };
+        if chord_held {
+            let held_bass = keypad_in.held_keys().into_iter()
+                .filter(|held| Some(*held) != last_input)
+                .find_map(key_degree);
+            if held_bass.is_some() && held_bass != bass {
+                bass = held_bass;
+                replay = true;
+            }
+        } else {
+            bass = None;
+        }
+
+        // what the chords are built from this time round
+        let harmony = Harmony { key, octave: current_octave, tuning: &tuning, mode: &modes[mode_idx], chord_type };
+
+        // match keypad input
+        match keypad_in.get_keypad(last_input) {
+            // ZERO, EIGHT and NINE with D held - secondary dominants
+            Some(number) if shift_held && shift_chord(number).is_some() => {
+                if last_input != Some(number) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    if let Some(chord) = shift_chord(number) {
+                        play_chord(&mut manager, &sound, current_freq, &harmony, chord, bass, &mut perf);
+                    }
+                    shift_used = true;
+                }
+                last_input = Some(number);
+            },
+            // ZERO - Play root note
+            Some(keypad::Keypad::ZERO) => {
+                if last_input != Some(keypad::Keypad::ZERO) {
+                    hold = false;
+                    perf.voices.release_all();
+                    let correction = octave_correction(key, current_octave, current_freq, tuning.a4);
+                    manager.play(Box::new(perf.voices.voice(&sound, tuning.step_ratio(0, &modes[mode_idx].steps) * correction, perf.gate)));
+                }
+                last_input = Some(keypad::Keypad::ZERO);
+            },
+            // ONE - chord on the 1st degree of the mode
+            Some(keypad::Keypad::ONE) => {
+                if last_input != Some(keypad::Keypad::ONE) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    play_chord(&mut manager, &sound, current_freq, &harmony, degree_chord(1, held_quality, shift_held), bass, &mut perf);
+                    modifier_used |= quality_held;
+                    shift_used |= shift_held;
+                }
+                last_input = Some(keypad::Keypad::ONE);
+            },
+            // TWO - chord on the 2nd degree
+            Some(keypad::Keypad::TWO) => {
+                if last_input != Some(keypad::Keypad::TWO) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    play_chord(&mut manager, &sound, current_freq, &harmony, degree_chord(2, held_quality, shift_held), bass, &mut perf);
+                    modifier_used |= quality_held;
+                    shift_used |= shift_held;
+                }
+                last_input = Some(keypad::Keypad::TWO);
+            },
+            // THREE - chord on the 3rd degree
+            Some(keypad::Keypad::THREE) => {
+                if last_input != Some(keypad::Keypad::THREE) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    play_chord(&mut manager, &sound, current_freq, &harmony, degree_chord(3, held_quality, shift_held), bass, &mut perf);
+                    modifier_used |= quality_held;
+                    shift_used |= shift_held;
+                }
+                last_input = Some(keypad::Keypad::THREE);
+            },
+            // FOUR - chord on the 4th degree
+            Some(keypad::Keypad::FOUR) => {
+                if last_input != Some(keypad::Keypad::FOUR) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    play_chord(&mut manager, &sound, current_freq, &harmony, degree_chord(4, held_quality, shift_held), bass, &mut perf);
+                    modifier_used |= quality_held;
+                    shift_used |= shift_held;
+                }
+                last_input = Some(keypad::Keypad::FOUR);
+            },
+            // FIVE - chord on the 5th degree
+            Some(keypad::Keypad::FIVE) => {
+                if last_input != Some(keypad::Keypad::FIVE) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    play_chord(&mut manager, &sound, current_freq, &harmony, degree_chord(5, held_quality, shift_held), bass, &mut perf);
+                    modifier_used |= quality_held;
+                    shift_used |= shift_held;
+                }
+                last_input = Some(keypad::Keypad::FIVE);
+            },
+            // SIX - chord on the 6th degree
+            Some(keypad::Keypad::SIX) => {
+                if last_input != Some(keypad::Keypad::SIX) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    play_chord(&mut manager, &sound, current_freq, &harmony, degree_chord(6, held_quality, shift_held), bass, &mut perf);
+                    modifier_used |= quality_held;
+                    shift_used |= shift_held;
+                }
+                last_input = Some(keypad::Keypad::SIX);
+            },
+           Some(keypad::Keypad::SEVEN)=> {
+                if last_input != Some(keypad::Keypad::SEVEN) || replay {
+                    hold = false;
+                    perf.voices.release_all();
+                    play_chord(&mut manager, &sound, current_freq, &harmony, degree_chord(7, held_quality, shift_held), bass, &mut perf);
+                    modifier_used |= quality_held;
+                    shift_used |= shift_held;
+                }
+                last_input = Some(keypad::Keypad::SEVEN);
+            },
+            // SEVEN - chord on the 7th degree
+                // ^^ Record current input
+                // if current input contains last input - do nothing, continue looping until sound is complete (with gate on)
+                // only if current input is Some() and different from previous, play chord (gate off)
+ 
+            // POUND - Hold playing chord - maybe use completion notifier to wait unless another input before completion. (does nothing with gate off)
+            Some(keypad::Keypad::POUND) if modifier_used => {
+                // the number key was let go but # is still down
+                if perf.gate && !hold {
+                    perf.voices.release_all();
+                }
+                if !hold {
+                    perf.arp.stop();
+                }
+                last_input = None;
+            },
+            Some(keypad::Keypad::POUND) => {
+                hold = true;
+            },
+            
+            None => {
+                if perf.gate && !hold {
+                    perf.voices.release_all();
+                }
+                // the arpeggiator only runs while the key is down or held with #
+                if !hold {
+                    perf.arp.stop();
+                }
+                last_input = None;
+            }
+
+            Some(keypad::Keypad::EIGHT)=> {
+                perf.voices.release_all();
+                match current_octave {
+                    Octave::LOW => {}
+                    Octave::MID => {
+                        current_octave = Octave::LOW;
+                        sleep(Duration::from_millis(INPUT_TIMEOUT));
+                    }
+                    Octave::HIGH => {
+                        current_octave = Octave::MID;
+                        sleep(Duration::from_millis(INPUT_TIMEOUT));
+                    }
+                }
+                last_input = Some(keypad::Keypad::EIGHT);
+            },
+            
+            Some(keypad::Keypad::NINE) => {
+                perf.voices.release_all();
+                match current_octave {
+                    Octave::LOW => {
+                        current_octave = Octave::MID;
+                        sleep(Duration::from_millis(INPUT_TIMEOUT));
+                    }
+                    Octave::MID => {
+                        current_octave = Octave::HIGH;
+                        sleep(Duration::from_millis(INPUT_TIMEOUT));
+                    }
+                    Octave::HIGH => {}
+                }
+                last_input = Some(keypad::Keypad::NINE);
+            },
+
+            // Below - only accept these inputs if current input == None
+            // A - Next mode
+            Some(keypad::Keypad::A) => {
+                perf.voices.release_all();
+                mode_idx = (mode_idx + 1) % modes.len();
+                sleep(Duration::from_millis(INPUT_TIMEOUT));
+                last_input = Some(keypad::Keypad::A);
+            },
+
+            // B - Gate On/Off
+            Some(keypad::Keypad::B) => {
+                perf.voices.release_all();
+                perf.gate = !perf.gate;
+                sleep(Duration::from_millis(INPUT_TIMEOUT));
+                last_input = Some(keypad::Keypad::B);
+            },
+            // C - TOF/Filter On/Off
+            Some(keypad::Keypad::C) => {
+                perf.voices.release_all();
+                if shared.tof_enabled_low.load(std::sync::atomic::Ordering::SeqCst) && shared.tof_enabled_high.load(std::sync::atomic::Ordering::SeqCst){
+                    shared.tof_enabled_high.store(false, std::sync::atomic::Ordering::SeqCst);
+                    println!("TOF HF");
+                } else if shared.tof_enabled_low.load(std::sync::atomic::Ordering::SeqCst) {
+                    shared.tof_enabled_low.store(false, std::sync::atomic::Ordering::SeqCst);
+                    shared.tof_enabled_high.store(true, std::sync::atomic::Ordering::SeqCst);
+                    println!("TOF LF");
+                } else if shared.tof_enabled_high.load(std::sync::atomic::Ordering::SeqCst) {
+                    shared.tof_enabled_low.store(false, std::sync::atomic::Ordering::SeqCst);
+                    shared.tof_enabled_high.store(false, std::sync::atomic::Ordering::SeqCst);
+                    println!("TOF off");
+                } else {
+                    shared.tof_enabled_low.store(true, std::sync::atomic::Ordering::SeqCst);
+                    shared.tof_enabled_high.store(true, std::sync::atomic::Ordering::SeqCst);
+                    println!("TOF on");
+                }
+                sleep(Duration::from_millis(INPUT_TIMEOUT));
+                last_input = Some(keypad::Keypad::C);
+            },
+            // D - Toggle Triads 7ths or 9ths when tapped (see the top of the loop), shift layer while held
+            Some(keypad::Keypad::D) => {
+                shift_pressed = true;
+                // the number key was let go but D is still down
+                if perf.gate && !hold {
+                    perf.voices.release_all();
+                }
+                if !hold {
+                    perf.arp.stop();
+                }
+                last_input = None;
+            },
+            // STAR - Record sample
+            Some(keypad::Keypad::STAR) => {
+                perf.voices.release_all();
+                perf.arp.stop();
+                let mut sound_dat = None;
+                // arecord needs the sound card, record_sample opens it again after
+                drop(manager);
+                drop(backend);
+                (backend, manager, sound_dat) = record_sample(media_path.clone(), &mut sample_paths, &mut current_sample_idx, &mut next_sample_no, &mut Panel { keypad_in: &mut keypad_in, enc_a: &enc_a, enc_b: &enc_b, display: &mut display }, &shared, &pitch::DetectParams::from_settings(&settings));
+                
+                match sound_dat {
+                    Some((new_snd, new_freq)) => {
+                        sound = new_snd;
+                        current_freq = new_freq;
+                    }
+                    None => {}
+                }
+                last_input = Some(keypad::Keypad::STAR);
+            },
+
+            // To keep rust compiler happy, this accounts for Volume or Key change
+            _ => {}
+        }
+
+        // the arpeggiator's next note, each one cuts off the one before
+        if let Some(speed) = perf.arp.step() {
+            perf.voices.release_all();
+            manager.play(Box::new(perf.voices.voice(&sound, speed, perf.gate)));
+        }
+
+        
+        // if volume - previous encoder value is different from current encoder value
+        let cur_counter_a = enc_a.count();
+        if cur_counter_a != last_counter_a {
+            let vol_diff: i64 = cur_counter_a - last_counter_a;
+            let new_vol = volume + vol_diff;
+            
+            volume = if new_vol > 75 {
+                75
+            } else if new_vol < 0 {
+                0
+            } else {
+                new_vol
+            };
+
+            let pre_rec_tof_high = shared.tof_enabled_high.load(std::sync::atomic::Ordering::SeqCst);
+            let pre_rec_tof_low = shared.tof_enabled_low.load(std::sync::atomic::Ordering::SeqCst);
+            shared.tof_enabled_high.store(false, std::sync::atomic::Ordering::SeqCst);
+            shared.tof_enabled_low.store(false, std::sync::atomic::Ordering::SeqCst);
+
+
+            set_volume(&shared.mixer, volume);
+
+            shared.tof_enabled_high.store(pre_rec_tof_high, std::sync::atomic::Ordering::SeqCst);
+            shared.tof_enabled_low.store(pre_rec_tof_low, std::sync::atomic::Ordering::SeqCst);
+            fullscreen_msg(&mut display, format!("Volume: {}%", (100.0 * (volume as f32 / 75.0)).round() as u16));
+            sleep(Duration::from_millis(FULLSCREEN_TIMEOUT));
+            last_input = Some(keypad::Keypad::VOL);
+        } else {
+            update_display(&mut display, &ScreenState { key, mode: &modes[mode_idx], octave: current_octave, tof_low: shared.tof_enabled_low.load(std::sync::atomic::Ordering::SeqCst), tof_high: shared.tof_enabled_high.load(std::sync::atomic::Ordering::SeqCst), hpf: shared.cur_hpf.load(std::sync::atomic::Ordering::SeqCst), lpf: shared.cur_lpf.load(std::sync::atomic::Ordering::SeqCst), chord_type, held_quality, shift_held, bass, voicing: perf.voicing, arp: &perf.arp, gate: perf.gate, tuning: &tuning });
+        }
+        last_counter_a = cur_counter_a;
+        
+        let mut cur_counter_b = enc_b.count();
+        if quality_held {
+            // with # down the key encoder picks the quality instead of the key
+            if cur_counter_b != last_counter_b {
+                quality = quality.next(cur_counter_b - last_counter_b);
+            }
+        } else if last_input.is_none() {
+            // if audio output change - volume encoder push button
+            if enc_a.is_pressed() {
+                int_io = set_io(int_io, &mut display, &shared.mixer);
+                last_input = Some(keypad::Keypad::IO);
+            }
+
+            // if root note change - previous encoder value is different from current 
+            // (turns with the button down change the voicing instead)
+            if cur_counter_b != last_counter_b && !enc_b.is_pressed() {
+                perf.voices.release_all();
+                let key_diff: i64 = cur_counter_b - last_counter_b;
+                let new_idx: i64= key_idx + key_diff;
+            
+                key_idx = if new_idx > 11 {
+                    11
+                } else if new_idx < 0 {
+                    0
+                } else {
+                    new_idx
+                };
+
+                key = KEYS[key_idx as usize];
+                update_display(&mut display, &ScreenState { key, mode: &modes[mode_idx], octave: current_octave, tof_low: shared.tof_enabled_low.load(std::sync::atomic::Ordering::SeqCst), tof_high: shared.tof_enabled_high.load(std::sync::atomic::Ordering::SeqCst), hpf: shared.cur_hpf.load(std::sync::atomic::Ordering::SeqCst), lpf: shared.cur_lpf.load(std::sync::atomic::Ordering::SeqCst), chord_type, held_quality, shift_held, bass, voicing: perf.voicing, arp: &perf.arp, gate: perf.gate, tuning: &tuning });
+                last_input = Some(keypad::Keypad::KEY);
+            }
+
+            // if file select toggle - enter sample select mode if in playback, long press for settings, push and turn for voicing
+            if enc_b.is_pressed() {
+                perf.voices.release_all();
+                let press_start = Instant::now();
+                let mut last_turn = last_counter_b;
+                let mut turned = false;
+                while enc_b.is_pressed() && (turned || press_start.elapsed() < Duration::from_millis(LONG_PRESS)) {
+                    let turn = enc_b.count();
+                    if turn != last_turn {
+                        perf.voicing = perf.voicing.next(turn - last_turn);
+                        last_turn = turn;
+                        turned = true;
+                        update_display(&mut display, &ScreenState { key, mode: &modes[mode_idx], octave: current_octave, tof_low: shared.tof_enabled_low.load(std::sync::atomic::Ordering::SeqCst), tof_high: shared.tof_enabled_high.load(std::sync::atomic::Ordering::SeqCst), hpf: shared.cur_hpf.load(std::sync::atomic::Ordering::SeqCst), lpf: shared.cur_lpf.load(std::sync::atomic::Ordering::SeqCst), chord_type, held_quality, shift_held, bass, voicing: perf.voicing, arp: &perf.arp, gate: perf.gate, tuning: &tuning });
+                    }
+                    sleep(Duration::from_millis(10));
+                }
+                if turned {
+                    cur_counter_b = last_turn;
+                } else if enc_b.is_pressed() {
+                    if settings::settings_menu(&mut settings, &scala_files, &enc_a, &enc_b, &mut display) {
+                        tuning = load_tuning(&settings, &media_path, &mut display);
+                        perf.configure(&settings);
+                        shared.filter.set_resonance(settings.resonance);
+                    }
+                    // the encoders were used by the menu, don't apply their turns again
+                    last_counter_a = enc_a.count();
+                    cur_counter_b = enc_b.count();
+                } else {
+                    match sample_select(&sample_paths, &mut current_sample_idx, &enc_b, &mut cur_counter_b, &pitch::DetectParams::from_settings(&settings), &mut display) {
+                        Some((new_sound, new_freq)) => {
+                            current_freq = new_freq;
+                            sound = new_sound;
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+        last_counter_b = cur_counter_b;
+    }    
+}
+
+// File names of the Scala tunings on the media drive.
+fn find_scala_files(media_path: &str) -> Vec<String> {
+    let mut scala_files: Vec<String> = match fs::read_dir(media_path) {
+        Ok(dir) => dir.filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".scl"))
+            .collect(),
+        Err(_) => Vec::new()
+    };
+    scala_files.sort();
+    scala_files
+}
+
+// Falls back to equal temperament if the selected tuning can't be loaded (e.g.
+// the drive with the .scl on it was removed).
+fn load_tuning<D: Screen>(settings: &Settings, media_path: &str, display: &mut D) -> Tuning {
+    match Tuning::new(settings.a4, &settings.temperament, settings.edo_steps, std::path::Path::new(media_path)) {
+        Ok(tuning) => tuning,
+        Err(e) => {
+            eprintln!("failed to load tuning: {}", e);
+            fullscreen_msg(display, "Err tuning!".to_string());
+            sleep(Duration::from_secs(1));
+            Tuning::equal(settings.a4)
+        }
+    }
+}
+
+// First drive found under '/media/<user>/', None if nothing is mounted.
+fn find_usb_media() -> Option<std::path::PathBuf> {
+    let mut media_users = fs::read_dir("/media").ok()?; // list users
+    let user_media = media_users.next()?.ok()?; // get user dir
+    let mut user_media_dir = fs::read_dir(user_media.path()).ok()?; // list user drives
+    Some(user_media_dir.next()?.ok()?.path()) // get user drive
+}
+
+// Chord a degree key plays with the modifiers held, # wins if both are down.
+fn degree_chord(degree: u16, quality: Option<Quality>, shift: bool) -> Chord {
+    match (quality, shift) {
+        (Some(quality), _) => Chord::Quality(degree, quality),
+        (None, true) => Chord::Borrowed(degree),
+        (None, false) => Chord::Diatonic(degree),
+    }
+}
+
+// Degree a number key picks as a slash chord's bass, 0 is the root like 1.
+fn key_degree(key: keypad::Keypad) -> Option<u16> {
+    match key {
+        keypad::Keypad::ZERO | keypad::Keypad::ONE => Some(1),
+        keypad::Keypad::TWO => Some(2),
+        keypad::Keypad::THREE => Some(3),
+        keypad::Keypad::FOUR => Some(4),
+        keypad::Keypad::FIVE => Some(5),
+        keypad::Keypad::SIX => Some(6),
+        keypad::Keypad::SEVEN => Some(7),
+        _ => None
+    }
+}
+
+// Secondary dominants on the keys left over on the shift layer.
+fn shift_chord(key: keypad::Keypad) -> Option<Chord> {
+    match key {
+        keypad::Keypad::EIGHT => Some(Chord::SecondaryDominant(5)),
+        keypad::Keypad::NINE => Some(Chord::SecondaryDominant(6)),
+        keypad::Keypad::ZERO => Some(Chord::SecondaryDominant(2)),
+        _ => None
+    }
+}
+
+// Ratio above the key's root for each note of a chord.
+fn chord_notes(tuning: &Tuning, mode: &Mode, chord: Chord, chord_type: u16) -> Vec<f64> {
+    match chord {
+        Chord::Diatonic(degree) => scale::chord_steps(degree, chord_type).into_iter()
+            .map(|step| tuning.step_ratio(step, &mode.steps))
+            .collect(),
+        Chord::Quality(degree, quality) => {
+            let chord_root = mode.semitones(degree.max(1) as usize - 1);
+            quality.intervals().iter().map(|interval| tuning.ratio(chord_root + interval)).collect()
+        }
+        Chord::Borrowed(degree) => {
+            let parallel = scale::parallel_steps(mode);
+            scale::chord_steps(degree, chord_type).into_iter()
+                .map(|step| tuning.ratio(parallel[step % parallel.len()] + 12 * (step / parallel.len()) as i32))
+                .collect()
+        }
+        Chord::SecondaryDominant(degree) => {
+            // a 5th above the degree, folded back into the first octave
+            let chord_root = (mode.semitones(degree.max(1) as usize - 1) + 7) % 12;
+            scale::DOMINANT.iter().take(chord_type as usize).map(|interval| tuning.ratio(chord_root + interval)).collect()
+        }
+    }
+}
+
+// Ratio above the key's root for a slash chord's bass, the degree an octave
+// down and further still if the voicing reaches below it. voiced are the
+// chord's pitches after the voicing.
+fn bass_ratio(tuning: &Tuning, mode: &Mode, degree: u16, root_pitch: f64, voiced: &[f64]) -> f64 {
+    let lowest = voiced.iter().copied().fold(f64::MAX, f64::min);
+    let mut ratio = tuning.step_ratio(degree.max(1) as usize - 1, &mode.steps) / 2.0;
+    while root_pitch + scale::pitch(ratio) > lowest - 0.5 {
+        ratio /= 2.0;
+    }
+    ratio
+}
+
+// How chords are being played, owned by run and handed to play_chord whole.
+struct Performance {
+    // push and turn the key encoder to change it
+    voicing: Voicing,
+    // the Lead voicing keeps every note between these, semitones from the root
+    lead_range: (i32, i32),
+    // order and ms between the notes of a strum
+    strum: (Strum, u16),
+    gate: bool,
+    // pitches of the last chord played, for the Lead voicing
+    last_chord: Vec<f64>,
+    // steps through the chord instead of playing it at once when it's on in the settings
+    arp: Arpeggiator,
+    // every note playing, gated or stolen from here
+    voices: VoiceManager,
+}
+
+impl Performance {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            voicing: Voicing::Root,
+            lead_range: (settings.lead_low, settings.lead_high),
+            strum: (settings.strum, settings.strum_time),
+            gate: false,
+            last_chord: Vec::new(),
+            arp: Arpeggiator::new(settings),
+            voices: VoiceManager::new(voice::MAX_VOICES, Adsr::from_settings(settings)),
+        }
+    }
+
+    // Picks up changes from the settings menu.
+    fn configure(&mut self, settings: &Settings) {
+        self.lead_range = (settings.lead_low, settings.lead_high);
+        self.strum = (settings.strum, settings.strum_time);
+        self.arp.configure(settings);
+        self.voices.set_envelope(Adsr::from_settings(settings));
+    }
+}
+
+// What a chord's notes come from: the key and octave it's rooted on, the tuning
+// and mode and how many notes to stack.
+struct Harmony<'a> {
+    key: Key,
+    octave: Octave,
+    tuning: &'a Tuning,
+    mode: &'a Mode,
+    chord_type: u16,
+}
+
+fn play_chord(manager: &mut Manager, sound: &Sample, freq: f64, harmony: &Harmony, chord: Chord, bass: Option<u16>, perf: &mut Performance) {
+    let Harmony { key, octave, tuning, mode, chord_type } = *harmony;
+    let correction = octave_correction(key, octave, freq, tuning.a4);
+    let notes = chord_notes(tuning, mode, chord, chord_type);
+    let root = key.frequency(tuning.a4) * octave.multiplier();
+    let pitches: Vec<f64> = notes.iter().map(|ratio| scale::pitch(root * ratio)).collect();
+    let low = scale::pitch(root) + perf.lead_range.0 as f64;
+    let high = scale::pitch(root) + perf.lead_range.1 as f64;
+    let shifts = perf.voicing.shifts(&pitches, &perf.last_chord, low, high);
+    perf.last_chord = pitches.iter().zip(&shifts).map(|(p, shift)| p + 12.0 * *shift as f64).collect();
+    let bass_ratio = bass.map(|bass| bass_ratio(tuning, mode, bass, scale::pitch(root), &perf.last_chord));
+    if perf.arp.is_on() {
+        let mut speeds: Vec<f64> = notes.iter().zip(&shifts).map(|(ratio, shift)| ratio * 2.0_f64.powi(*shift) * correction).collect();
+        // As Played starts from the bass like it would by hand
+        if let Some(bass_ratio) = bass_ratio {
+            speeds.insert(0, bass_ratio * correction);
+        }
+        perf.arp.start(speeds);
+        return;
+    }
+    // each note with its pitch, so a strum knows which end to start from
+    let mut chord_snds = Vec::new();
+    for ((ratio, shift), pitch) in notes.into_iter().zip(shifts).zip(perf.last_chord.iter()) {
+        chord_snds.push((*pitch, perf.voices.voice(sound, ratio * 2.0_f64.powi(shift) * correction, perf.gate)));
+    }
+    // the bass isn't part of last_chord so Lead only follows the chord itself
+    if let Some(bass_ratio) = bass_ratio {
+        chord_snds.push((scale::pitch(root * bass_ratio), perf.voices.voice(sound, bass_ratio * correction, perf.gate)));
+    }
+    strum::play_strummed(manager, chord_snds, perf.strum.0, perf.strum.1);
+}
+
+// Shows what was detected for a second, a low % means it's worth checking by ear.
+fn show_detection<D: Screen>(display: &mut D, detection: &pitch::Detection) {
+    if let Some(fallback) = detection.fallback {
+        fullscreen_msg(display, format!("Used {}", fallback.name()));
+        sleep(Duration::from_secs(1));
+    }
+    fullscreen_msg(display, format!("{:.1}Hz {:.0}%", detection.frequency, detection.confidence * 100.0));
+    sleep(Duration::from_secs(1));
+}
+
+// Root frequency of a sample from its sidecar, or detected and written there
+// so it loads straight away next time. Err is the message to show.
+fn sample_root<D: Screen>(sound: &mut Sample, wav_path: &str, detect_params: &pitch::DetectParams, display: &mut D) -> Result<f64, &'static str> {
+    if let Some(root) = sound.meta.root() {
+        return Ok(root);
+    }
+    fullscreen_msg(display, "Processing...".to_string());
+    if sound.frames() < SIZE {
+        return Err("Too short!");
+    }
+    let detection = sound.detect(detect_params).ok_or("Err no pitch!")?;
+    show_detection(display, &detection);
+    sound.meta.frequency = Some(detection.frequency);
+    sound.meta.confidence = Some(detection.confidence);
+    sound.meta.save(path::Path::new(wav_path));
+    sound.set_root(detection.frequency);
+    Ok(detection.frequency)
+}
+
+fn sample_select(sample_paths: &Vec<String>, current_smpl_idx: &mut usize, encoder: &impl Encoder, cur_enc_cnt: &mut i64, detect_params: &pitch::DetectParams, display: &mut impl Screen) -> Option<(Sample, f64)> {
+    // start on current sample
+
+    // while root note encoder push button has not been pressed
+        // determine encoder left and right to cycle through discovered samples
+        // set new sample file as the current sample
+    // detect frequency and record
+    fullscreen_msg(display, "Sample Select".to_string());
+    sleep(Duration::from_millis(1000));
+
+    let mut cur_smpl = sample_paths[*current_smpl_idx].clone();
+    let mut name_begin = cur_smpl.rfind("/").unwrap_or(0);
+    if name_begin > 0 {
+        name_begin += 1;
+    }
+    let trunc_smpl = &cur_smpl[name_begin..cur_smpl.len()-4];
+    let trunc_smpl_string = if trunc_smpl.len() > 16 {
+        trunc_smpl[0..16].to_string()
+    } else {
+        trunc_smpl.to_string()
+    };
+    fullscreen_msg(display, trunc_smpl_string);
+    sleep(Duration::from_millis(500));
+    
+    let mut last_enc_cnt = *cur_enc_cnt;
+    loop {
+        *cur_enc_cnt = encoder.count();
+
+        if *cur_enc_cnt != last_enc_cnt {
+            let enc_diff: i64 = *cur_enc_cnt - last_enc_cnt;
+            let new_idx: i64 = (*current_smpl_idx as i64) + enc_diff;
+            
+            *current_smpl_idx = if new_idx >= (sample_paths.len() as i64) {
+                0
+            } else if new_idx < 0 {
+                sample_paths.len() - 1
+            } else {
+                new_idx as usize
+            };
+        }
+        
+        cur_smpl = sample_paths[*current_smpl_idx].clone();
+        let mut name_begin = cur_smpl.rfind("/").unwrap_or(0);
+        if name_begin > 0 {
+            name_begin += 1;
+        }
+        let trunc_smpl = &cur_smpl[name_begin..cur_smpl.len()-4];
+        let trunc_smpl_string = if trunc_smpl.len() > 16 {
+            trunc_smpl[0..16].to_string()
+        } else {
+            trunc_smpl.to_string()
+        };
+        fullscreen_msg(display, trunc_smpl_string);
+        //sleep(Duration::from_millis(500));
+
+        if encoder.is_pressed() {
+            break;
+        }
+        last_enc_cnt = *cur_enc_cnt;
+    }
+    let test_sound = match sample::open_wav(path::Path::new(&cur_smpl)) {
+        Ok(sound) => sound,
+        Err(e) => {
+            eprintln!("failed to open {}: {}", cur_smpl, e);
+            fullscreen_msg(display, "Err opening!".to_string());
+            sleep(Duration::from_secs(1));
+            return None
+        }
+    };
+
+    let mut out_sound = Sample::new(test_sound, SampleMeta::load(path::Path::new(&cur_smpl)));
+    let out_freq = match sample_root(&mut out_sound, &cur_smpl, detect_params, display) {
+        Ok(freq) => freq,
+        Err(msg) => {
+            fullscreen_msg(display, msg.to_string());
+            sleep(Duration::from_secs(1));
+            return None
+        }
+    };
+
+    Some((out_sound, out_freq))
+}
+
+// Corrects the root of the loaded sample by ear when the detector picked an
+// octave or a harmonic. The volume encoder switches between semitones and
+// cents, the key encoder changes them and pressing it saves the override to
+// the sample's sidecar and leaves. Holding 0 plays a sine at the note nearest
+// the root and holding 1 plays the sample pitched to that note, they're in
+// tune when the root is right. Returns the root to play the sample from.
+fn sample_edit<K: KeypadInput, E: Encoder, D: Screen>(sound: &mut Sample, freq: f64, wav_path: &str, a4: f64, playback: &mut Playback, panel: &mut Panel<K, E, D>) -> f64 {
+    let (manager, voices) = (&mut *playback.manager, &mut *playback.voices);
+    let (keypad_in, enc_a, enc_b, display) = (&mut *panel.keypad_in, panel.enc_a, panel.enc_b, &mut *panel.display);
+    voices.release_all();
+    // the override is kept as cents from what was detected
+    let detected = sound.meta.frequency.unwrap_or(freq);
+    let original: i64 = (1200.0 * (freq / detected).log2()).round() as i64;
+    let mut offset = original;
+    let mut cents_item = false;
+    let mut last_cnt_a = enc_a.count();
+    let mut last_cnt_b = enc_b.count();
+    let mut tone: Option<sounds::wrappers::Controller<sounds::wrappers::Stoppable<sounds::wrappers::AdjustableVolume<sounds::SineWave>>>> = None;
+    let mut sample_playing = false;
+    loop {
+        let cur_cnt_a = enc_a.count();
+        if cur_cnt_a != last_cnt_a {
+            cents_item = !cents_item;
+        }
+        last_cnt_a = cur_cnt_a;
+
+        let cur_cnt_b = enc_b.count();
+        if cur_cnt_b != last_cnt_b {
+            let step = if cents_item { 1 } else { 100 };
+            offset = (offset + (cur_cnt_b - last_cnt_b) * step).clamp(-ROOT_RANGE, ROOT_RANGE);
+            // the reference starts again at the new note while it's still held
+            if let Some(mut tone) = tone.take() {
+                tone.set_stopped();
+            }
+            if sample_playing {
+                voices.release_all();
+                sample_playing = false;
+            }
+        }
+        last_cnt_b = cur_cnt_b;
+
+        let root = detected * 2.0_f64.powf(offset as f64 / 1200.0);
+        let note = a4 * 2.0_f64.powf((12.0 * (root / a4).log2()).round() / 12.0);
+        if keypad_in.is_held(keypad::Keypad::ZERO) {
+            if tone.is_none() {
+                let (tone_snd, controller) = sounds::SineWave::with_sample_rate(note as f32, SAMPLE_RATE as u32)
+                    .with_adjustable_volume_of(REFERENCE_VOLUME)
+                    .stoppable()
+                    .controllable();
+                manager.play(Box::new(tone_snd));
+                tone = Some(controller);
+            }
+        } else if let Some(mut tone) = tone.take() {
+            tone.set_stopped();
+        }
+        if keypad_in.is_held(keypad::Keypad::ONE) {
+            if !sample_playing {
+                manager.play(Box::new(voices.voice(sound, note / root, true)));
+                sample_playing = true;
+            }
+        } else if sample_playing {
+            voices.release_all();
+            sample_playing = false;
+        }
+
+        let semitones = (offset as f64 / 100.0).round() as i64;
+        let (name, value) = if cents_item {
+            ("Root Cents", offset - semitones * 100)
+        } else {
+            ("Root Semitones", semitones)
+        };
+        settings::menu_msg(display, name, &format!("{:.1}Hz {:+}", root, value));
+
+        if enc_b.is_pressed() {
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+    if let Some(mut tone) = tone.take() {
+        tone.set_stopped();
+    }
+    voices.release_all();
+    while enc_b.is_pressed() {
+        sleep(Duration::from_millis(10));
+    }
+
+    if offset == original {
+        return freq;
+    }
+    let root = detected * 2.0_f64.powf(offset as f64 / 1200.0);
+    // back on the detected root the override isn't needed any more
+    sound.meta.root_override = if offset == 0 && sound.meta.frequency.is_some() {
+        None
+    } else {
+        Some(root)
+    };
+    sound.meta.save(path::Path::new(wav_path));
+    sound.set_root(root);
+    fullscreen_msg(display, "Root saved".to_string());
+    sleep(Duration::from_secs(1));
+    root
+}
+
+// Opens the sound card with the filter in front of it. Chords are played on the
+// returned manager, which mixes into the filter rather than going straight out.
+fn start_audio(filter: &Arc<FilterParams>) -> (CpalBackend, Manager) {
+    let mut backend =
+        backends::CpalBackend::with_default_host_and_device(1,48000,CpalBufferSize::Default).ok_or(backends::CpalBackendError::NoDevice).expect("failed to initilize cpal backend!");
+    let mut output = backend.start(|error| eprintln!("error with cpal output stream: {}", error)).expect("failed to initialize sound manager!");
+    let (chord_manager, mut chord_renderer) = Manager::new();
+    chord_renderer.set_output_channel_count_and_sample_rate(1, SAMPLE_RATE as u32);
+    // the filter never finishes so the output keeps going without its manager
+    output.play(Box::new(Filter::new(chord_renderer, filter.clone())));
+    (backend, chord_manager)
+}
+
+// Records from the sound card, which has to be let go of first, and opens it
+// again after. The ToF sensor is paused for the whole time.
+fn record_sample<K: KeypadInput, E: Encoder, D: Screen>(media_path: String, sample_paths: &mut Vec<String>, current_smpl_idx: &mut usize, next_smpl_no: &mut usize, panel: &mut Panel<K, E, D>, shared: &SharedState, detect_params: &pitch::DetectParams) -> (CpalBackend, Manager, Option<(Sample, f64)>) {
+    let pre_rec_tof_high = shared.tof_enabled_high.load(std::sync::atomic::Ordering::SeqCst);
+    let pre_rec_tof_low = shared.tof_enabled_low.load(std::sync::atomic::Ordering::SeqCst);
+    shared.tof_enabled_high.store(false, std::sync::atomic::Ordering::SeqCst);
+    shared.tof_enabled_low.store(false, std::sync::atomic::Ordering::SeqCst);
+    let recorded = record(media_path, sample_paths, current_smpl_idx, next_smpl_no, panel, detect_params);
+    shared.tof_enabled_high.store(pre_rec_tof_high, std::sync::atomic::Ordering::SeqCst);
+    shared.tof_enabled_low.store(pre_rec_tof_low, std::sync::atomic::Ordering::SeqCst);
+    let (backend, manager) = start_audio(&shared.filter);
+    (backend, manager, recorded)
+}
+
+fn record<K: KeypadInput, E: Encoder, D: Screen>(media_path: String, sample_paths: &mut Vec<String>, current_smpl_idx: &mut usize, next_smpl_no: &mut usize, panel: &mut Panel<K, E, D>, detect_params: &pitch::DetectParams) -> Option<(Sample, f64)> {
+    let keypad_in = &mut *panel.keypad_in;
+    let display = &mut *panel.display;
+
+//     // give countdown
+//     // record sample
+//     // detect frequency
+    //manager = backends::CpalBackend::new(1, 48000, CpalBufferSize::Default, cpal::platform::, sample_format)
+
+    let sample_name = format!("sound_{}.wav", next_smpl_no);
+    let rec_path = format!("{}/{}", media_path, sample_name);
+
+    fullscreen_msg(display, "Recording in 3".to_string());
+    sleep(Duration::from_secs(1));
+    fullscreen_msg(display, "Recording in 2".to_string());
+    sleep(Duration::from_secs(1));
+    fullscreen_msg(display, "Recording in 1".to_string());
+    sleep(Duration::from_secs(1));
+ 
+    let mut arec= match std::process::Command::new("arecord")
+        .args(vec!["-D", "plughw:1,0", "-f", "S32_LE", "-c", "1", "-r", "48000", rec_path.as_str()])
+        .spawn() {
+            Ok(arec) => arec,
+            Err(_) => {
+                fullscreen_msg(display, "Recording fail!".to_string());
+                sleep(Duration::from_secs(1));
+                return None
+            }
+        };
+    
+    fullscreen_msg(display, "Recording...".to_string());
+
+    let rec_start_time = Instant::now();
+    // Sample up to 10 minutes!
+    let max_rec_time = Duration::from_secs(600); 
+    loop {
+        match arec.try_wait() {
+            Ok(complete) => {
+                match complete {
+                    Some(_status) => break,
+                    None => {}
+                }
+            }
+            Err(_) => {
+                fullscreen_msg(display, "System error!".to_string());
+                sleep(Duration::from_secs(1));
+                return None
+            }
+        }
+
+        if keypad_in.get_keypad(None) == Some(keypad::Keypad::STAR) || rec_start_time.elapsed() > max_rec_time {
+            match nix::sys::signal::kill(nix::unistd::Pid::from_raw(arec.id() as i32), nix::sys::signal::Signal::SIGINT) {
+                Ok(_) => {},
+                Err(_) => {
+                    let _ = nix::sys::signal::kill(nix::unistd::Pid::from_raw(arec.id() as i32), nix::sys::signal::Signal::SIGKILL);
+                }
+            }
+            break;
+        }
+    }
+
+    fullscreen_msg(display, "Processing...".to_string());
+    sleep(Duration::from_millis(INPUT_TIMEOUT));
+    let test_sound = match sample::open_wav(path::Path::new(&rec_path)) {
+        Ok(sound) => sound,
+        Err(e) => {
+            eprintln!("failed to open {}: {}", rec_path, e);
+            fullscreen_msg(display, "Err opening!".to_string());
+            sleep(Duration::from_secs(1));
+            return None
+        }
+    };
+
+    // a new recording has no sidecar yet so this always detects and writes one
+    let mut out_sample = Sample::new(test_sound, SampleMeta::load(path::Path::new(&rec_path)));
+    let out_freq = match sample_root(&mut out_sample, &rec_path, detect_params, display) {
+        Ok(freq) => freq,
+        Err(msg) => {
+            fullscreen_msg(display, msg.to_string());
+            sleep(Duration::from_secs(1));
+            return None
+        }
+    };
+    
+    *next_smpl_no += 1;
+    sample_paths.push(rec_path);
+    *current_smpl_idx = sample_paths.len() - 1;
+
+    Some((out_sample, out_freq))
+}
+
+// What the main screen shows, gathered from the loop each time it's drawn.
+struct ScreenState<'a> {
+    key: Key,
+    mode: &'a Mode,
+    octave: Octave,
+    tof_low: bool,
+    tof_high: bool,
+    hpf: u16,
+    lpf: u16,
+    chord_type: u16,
+    // the modifiers take the chord type's place while they're down
+    held_quality: Option<Quality>,
+    shift_held: bool,
+    bass: Option<u16>,
+    voicing: Voicing,
+    arp: &'a Arpeggiator,
+    gate: bool,
+    tuning: &'a Tuning,
+}
+
+fn update_display<D: Screen>(display: &mut D, state: &ScreenState) {
+    let ScreenState { key, mode, octave, tof_low, tof_high, hpf, lpf, chord_type, held_quality, shift_held, bass, voicing, arp, gate, tuning } = *state;
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_8X13)
+        .text_color(BinaryColor::On)
+        .build();
+
+    let key_text: String = format!("Key:{:#?}", key); 
+    let mode_text: String = format!("Md:{}", mode.abbrev());
+    let oct_text = match octave {
+        Octave::LOW => format!("Oct:Low"),
+        Octave::MID => format!("Oct:Mid"),
+        Octave::HIGH => format!("Oct:Hi")
+    };
+
+    let chord_text: String = match (held_quality, shift_held, bass, chord_type) {
+        (Some(quality), _, _, _) => format!("#:{}", quality.abbrev()),
+        (None, true, _, _) => "D:Shift".to_string(),
+        (None, false, Some(bass), _) => format!("Bass:{}", bass),
+        (None, false, None, TRIADS) => format!("Typ:Tri"),
+        (None, false, None, SEVENTHS) => format!("Typ:7th"),
+        (None, false, None, NINTHS) => format!("Typ:9th"),
+        _ => format!("Typ:Tri"),
+    };   
+
+    let tof_text: String = if tof_low && tof_high {
+        format!("TOF:On")
+    } else if tof_low {
+        format!("TOF:HF")
+    } else if tof_high {
+        format!("TOF:LF")
+    } else {
+        format!("TOF:Off")
+    };
+
+    let hpf_text: String = format!("HF:{:#?}", hpf); 
+    let lpf_text: String = format!("LF:{:#?}", lpf);
+
+    // small status line along the bottom
+    let status_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::On)
+        .build();
+    // the arpeggiator's pattern takes the tuning's place while it's on
+    let tuning_text: String = if arp.is_on() {
+        format!("{} {}", voicing.abbrev(), arp.status())
+    } else {
+        format!("{} A4:{}Hz {}", voicing.abbrev(), tuning.a4, tuning.name)
+    };
+
+    let gate_text: String = if gate {
+        format!("Gat:ON")
+    } else {
+        format!("Gat:OFF")
+    };
+
+    display.clear_buffer(); 
+    Text::with_baseline(&key_text, Point::new(2, 1), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&mode_text, Point::new(2, 14), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&oct_text, Point::new(2, 27), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&chord_text, Point::new(2, 40), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&hpf_text, Point::new(64, 1), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&lpf_text, Point::new(64, 14), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&tof_text, Point::new(64, 27), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&gate_text, Point::new(64, 40), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    Text::with_baseline(&tuning_text, Point::new(2, 54), status_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+
+    display.flush().unwrap(); 
+}
+
+fn fullscreen_msg<D: Screen>(display: &mut D, text: String) {
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_8X13)
+        .text_color(BinaryColor::On)
+        .build();
+
+    display.clear_buffer(); 
+
+    let x: i32 = 64 - (((text.len() as i32) * 8) / 2);
+    Text::with_baseline(&text, Point::new(x, 26), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+
+    display.flush().unwrap(); 
+
+}
+
+fn set_volume(mixer: &Mutex<Mixer>, volume: i64) {
+    let mut mixer = mixer.lock().expect("failed to lock mixer");
+    if let Err(e) = mixer.set_volume_percent(mixer::DAC_VOLUME, VOL_LUT[volume as usize]) {
+        eprintln!("failed to set volume: {}", e);
+    }
+}
+
+fn set_io<D: Screen>(int_io: bool, display: &mut D, mixer: &Mutex<Mixer>) -> bool {
+    if int_io {
+        fullscreen_msg(display, "I/O External".to_string());
+        let res = set_io_controls(mixer, 100, 0, true, 34, false, 0);
+        if let Err(e) = res {
+            eprintln!("failed to switch to external I/O: {}", e);
+        }
+        false
+    } else {
+        fullscreen_msg(display, "I/O Internal".to_string());
+        let res = set_io_controls(mixer, 0, 88, false, 0, true, 3);
+        if let Err(e) = res {
+            eprintln!("failed to switch to internal I/O: {}", e);
+        }
+        true
+    }
+}
+
+fn set_io_controls(mixer: &Mutex<Mixer>, headphone_vol: i64, lineout_vol: i64, aux_en: bool, aux_vol: i64, mems_mic_en: bool, mems_mic_vol: i64) -> Result<(), mixer::MixerError> {
+    let mut mixer = mixer.lock().expect("failed to lock mixer");
+    mixer.set_volume(mixer::HEADPHONE_VOLUME, headphone_vol)?;
+    mixer.set_volume(mixer::LINEOUT_VOLUME, lineout_vol)?;
+    mixer.set_switch(mixer::AUX_SWITCH, aux_en)?;
+    mixer.set_volume(mixer::AUX_VOLUME, aux_vol)?;
+    mixer.set_switch(mixer::MIC_SWITCH, mems_mic_en)?;
+    mixer.set_volume(mixer::MIC_VOLUME, mems_mic_vol)?;
+    Ok(())
+}
@@ -0,0 +1,171 @@
+// Codec controls, talks to the card's mixer directly instead of forking amixer
+// for every change. Controls are looked up by name so they don't depend on the
+// numid order of the running kernel, `amixer -c 1 scontrols` lists them. Each
+// one is checked against the card on its own, one that's missing or can't do
+// what it's used for is left out with a message and the rest still work.
+use std::{collections::HashMap, fmt};
+
+use alsa::mixer::{Selem, SelemId};
+
+const CARD: &str = "hw:1";
+
+// the old amixer numid is noted next to each control
+pub const MIC_VOLUME: &str = "Mic 2"; // numid=2
+pub const MIC_SWITCH: &str = "Mic 2"; // numid=77
+pub const AUX_VOLUME: &str = "Aux"; // numid=3
+pub const AUX_SWITCH: &str = "Aux"; // numid=78
+pub const DAC_VOLUME: &str = "DAC"; // numid=6
+pub const HEADPHONE_VOLUME: &str = "Headphone"; // numid=7
+pub const LINEOUT_VOLUME: &str = "Lineout"; // numid=8
+pub const EQ_SWITCH: &str = "DAC EQ"; // numid=9
+pub const EQ_BANDS: [&str; 5] = ["DAC EQ1", "DAC EQ2", "DAC EQ3", "DAC EQ4", "DAC EQ5"]; // numid=10-14
+
+// everything the instrument sets
+const SETTINGS: [Setting; 13] = [
+    Setting::Volume(MIC_VOLUME), Setting::Switch(MIC_SWITCH), Setting::Volume(AUX_VOLUME), Setting::Switch(AUX_SWITCH),
+    Setting::Volume(DAC_VOLUME), Setting::Volume(HEADPHONE_VOLUME), Setting::Volume(LINEOUT_VOLUME), Setting::Switch(EQ_SWITCH),
+    Setting::Volume(EQ_BANDS[0]), Setting::Volume(EQ_BANDS[1]), Setting::Volume(EQ_BANDS[2]), Setting::Volume(EQ_BANDS[3]), Setting::Volume(EQ_BANDS[4]),
+];
+// the mic and aux are inputs so they're set on the capture side
+const INPUTS: [&str; 2] = [MIC_VOLUME, AUX_VOLUME];
+
+#[derive(Debug)]
+pub enum MixerError {
+    NotFound(&'static str),
+    Alsa(&'static str, alsa::Error),
+}
+
+impl fmt::Display for MixerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MixerError::NotFound(name) => write!(f, "no mixer control named '{}'", name),
+            MixerError::Alsa(name, e) => write!(f, "failed to set '{}': {}", name, e),
+        }
+    }
+}
+
+impl std::error::Error for MixerError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Setting {
+    Volume(&'static str),
+    Switch(&'static str),
+}
+
+// Without the codec (in the simulator or on a dev machine) writes only go to
+// the cache, so everything above this behaves the same either way. The same
+// goes for the settings the card doesn't have.
+pub struct Mixer {
+    card: Option<alsa::mixer::Mixer>,
+    missing: Vec<Setting>,
+    // last value written to each control, repeats are skipped
+    cache: HashMap<Setting, i64>,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        let card = match alsa::mixer::Mixer::new(CARD, false) {
+            Ok(card) => Some(card),
+            Err(e) => {
+                eprintln!("failed to open mixer {}: {}, running without the codec", CARD, e);
+                None
+            }
+        };
+        let missing: Vec<Setting> = match &card {
+            Some(card) => SETTINGS.iter()
+                .filter(|setting| !supported(card, **setting))
+                .copied().collect(),
+            None => Vec::new()
+        };
+        if !missing.is_empty() {
+            eprintln!("{} can't do {:?}, they'll be skipped", CARD, missing);
+        }
+        Self {
+            card,
+            missing,
+            cache: HashMap::new(),
+        }
+    }
+
+    // raw control value, same as `amixer cset numid=x <value>`
+    pub fn set_volume(&mut self, name: &'static str, value: i64) -> Result<(), MixerError> {
+        self.write(Setting::Volume(name), value)
+    }
+
+    // percentage of the control's range, same as `amixer cset numid=x <value>%`
+    pub fn set_volume_percent(&mut self, name: &'static str, percent: u16) -> Result<(), MixerError> {
+        let value = match &self.card {
+            Some(card) if !self.missing.contains(&Setting::Volume(name)) => {
+                let selem = find(card, name)?;
+                let (min, max) = if capture_volume(&selem, name) {
+                    selem.get_capture_volume_range()
+                } else {
+                    selem.get_playback_volume_range()
+                };
+                min + ((max - min) as f64 * (percent.min(100) as f64 / 100.0)).round() as i64
+            }
+            _ => percent as i64
+        };
+        self.write(Setting::Volume(name), value)
+    }
+
+    pub fn set_switch(&mut self, name: &'static str, on: bool) -> Result<(), MixerError> {
+        self.write(Setting::Switch(name), on as i64)
+    }
+
+    fn write(&mut self, setting: Setting, value: i64) -> Result<(), MixerError> {
+        if self.cache.get(&setting) == Some(&value) {
+            return Ok(());
+        }
+        match &self.card {
+            Some(card) if !self.missing.contains(&setting) => match setting {
+                Setting::Volume(name) => {
+                    let selem = find(card, name)?;
+                    if capture_volume(&selem, name) {
+                        selem.set_capture_volume_all(value)
+                    } else {
+                        selem.set_playback_volume_all(value)
+                    }.map_err(|e| MixerError::Alsa(name, e))?;
+                }
+                Setting::Switch(name) => {
+                    let selem = find(card, name)?;
+                    if capture_switch(&selem, name) {
+                        selem.set_capture_switch_all(value as i32)
+                    } else {
+                        selem.set_playback_switch_all(value as i32)
+                    }.map_err(|e| MixerError::Alsa(name, e))?;
+                }
+            },
+            _ => {}
+        }
+        self.cache.insert(setting, value);
+        Ok(())
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn find<'a>(card: &'a alsa::mixer::Mixer, name: &'static str) -> Result<Selem<'a>, MixerError> {
+    card.find_selem(&SelemId::new(name, 0)).ok_or(MixerError::NotFound(name))
+}
+
+// whether the card has the control and it has a volume or switch to set
+fn supported(card: &alsa::mixer::Mixer, setting: Setting) -> bool {
+    match setting {
+        Setting::Volume(name) => find(card, name).is_ok_and(|selem| selem.has_playback_volume() || selem.has_capture_volume()),
+        Setting::Switch(name) => find(card, name).is_ok_and(|selem| selem.has_playback_switch() || selem.has_capture_switch()),
+    }
+}
+
+// inputs go through the capture side when the card has one for them
+fn capture_volume(selem: &Selem, name: &str) -> bool {
+    selem.has_capture_volume() && (INPUTS.contains(&name) || !selem.has_playback_volume())
+}
+
+fn capture_switch(selem: &Selem, name: &str) -> bool {
+    selem.has_capture_switch() && (INPUTS.contains(&name) || !selem.has_playback_switch())
+}
@@ -0,0 +1,264 @@
+// Root frequency of a whole sample. The detector only looks at SIZE samples at
+// a time, so it's run over windows all the way through and the answers are
+// combined, which gets past silence or a breath at the start of a recording
+// and isn't thrown by one odd window.
+use std::sync::Arc;
+
+use pitch_detection::{detector::{autocorrelation::AutocorrelationDetector, mcleod::McLeodDetector, yin::YINDetector, PitchDetector}, utils::buffer::square_sum, Pitch};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::{scale, settings::Settings, PADDING, SIZE};
+
+// windows spread over a long recording, enough for a good answer without it
+// taking ages on the Pi Zero
+const MAX_WINDOWS: usize = 200;
+// windows within this many semitones of the answer agree with it
+const AGREEMENT: f64 = 0.5;
+
+// limits of the settings, power is the sum of the squared samples in a window
+pub const POWER_MIN: f64 = 0.000001;
+pub const POWER_MAX: f64 = 1.0;
+pub const CLARITY_MIN: f64 = 0.05;
+pub const CLARITY_MAX: f64 = 0.95;
+
+// Different material suits different detectors: McLeod is a good all rounder,
+// autocorrelation is simple and copes with bass, YIN is steady on voices and
+// Spectral looks at the spectrum for harmonics that line up, which survives
+// noisy field recordings and a missing fundamental.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Detector {
+    #[default]
+    McLeod,
+    Autocorrelation,
+    Yin,
+    Spectral,
+}
+
+pub const DETECTORS: [Detector; 4] = [Detector::McLeod, Detector::Autocorrelation, Detector::Yin, Detector::Spectral];
+
+impl Detector {
+    pub fn next(self, steps: i64) -> Self {
+        let cur_idx = DETECTORS.iter().position(|detector| *detector == self).unwrap_or(0) as i64;
+        DETECTORS[(cur_idx + steps).rem_euclid(DETECTORS.len() as i64) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Detector::McLeod => "McLeod",
+            Detector::Autocorrelation => "Autocorrelation",
+            Detector::Yin => "YIN",
+            Detector::Spectral => "Spectral",
+        }
+    }
+
+    fn window_detector(self) -> Box<dyn PitchDetector<f64>> {
+        match self {
+            Detector::McLeod => Box::new(McLeodDetector::new(SIZE, PADDING)),
+            Detector::Autocorrelation => Box::new(AutocorrelationDetector::new(SIZE, PADDING)),
+            Detector::Yin => Box::new(YinDetector(YINDetector::new(SIZE, PADDING))),
+            Detector::Spectral => Box::new(SpectralDetector::new()),
+        }
+    }
+}
+
+// what the detection is done with, from the settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DetectParams {
+    pub detector: Detector,
+    // tried when the detector finds nothing
+    pub fallback: Option<Detector>,
+    // quieter windows are skipped
+    pub power_threshold: f64,
+    // 0-1, windows the detector is less sure of than this don't count
+    pub clarity_threshold: f64,
+}
+
+impl DetectParams {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            detector: settings.detector,
+            fallback: settings.fallback_detector,
+            power_threshold: settings.power_threshold.clamp(POWER_MIN, POWER_MAX),
+            clarity_threshold: settings.clarity_threshold.clamp(CLARITY_MIN, CLARITY_MAX),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detection {
+    pub frequency: f64,
+    // 0-1, how much of the sample that isn't silent agrees on the frequency
+    pub confidence: f64,
+    // the fallback detector if it was the one that found it
+    pub fallback: Option<Detector>,
+}
+
+// Runs the chosen detector over the sample (interleaved frames of channels),
+// then the fallback if that found nothing. None if the sample is shorter than
+// a window or neither has a pitch.
+pub fn detect(samples: &[i16], channels: usize, sample_rate: u32, params: &DetectParams) -> Option<Detection> {
+    let channels = channels.max(1);
+    if let Some(detection) = detect_with(samples, channels, sample_rate as usize, params.detector, params) {
+        return Some(detection);
+    }
+    let fallback = params.fallback.filter(|fallback| *fallback != params.detector)?;
+    detect_with(samples, channels, sample_rate as usize, fallback, params).map(|detection| Detection { fallback: Some(fallback), ..detection })
+}
+
+// Detects every window loud enough to have a pitch and takes the median of
+// their frequencies weighted by how clear each one was. Only the windows that
+// are looked at get converted, a long recording isn't copied out in full.
+fn detect_with(samples: &[i16], channels: usize, sample_rate: usize, detector: Detector, params: &DetectParams) -> Option<Detection> {
+    let frames = samples.len() / channels;
+    if frames < SIZE {
+        return None;
+    }
+    // half a window apart, or further on a long one
+    let hop = ((frames - SIZE) / MAX_WINDOWS).max(SIZE / 2);
+    let mut window_detector = detector.window_detector();
+    // (frequency, clarity) of each window with a pitch, and how many weren't silent
+    let mut pitches: Vec<(f64, f64)> = Vec::new();
+    let mut loud = 0;
+    let mut window = vec![0.0; SIZE];
+    for start in (0..=frames - SIZE).step_by(hop) {
+        // scaled to -1 to 1 with the channels mixed down
+        let scale = 1.0 / (32768.0 * channels as f64);
+        for (s, frame) in window.iter_mut().zip(samples[start * channels..(start + SIZE) * channels].chunks_exact(channels)) {
+            *s = frame.iter().map(|s| *s as f64).sum::<f64>() * scale;
+        }
+        let window = &window[..];
+        if square_sum(window) < params.power_threshold {
+            continue;
+        }
+        loud += 1;
+        if let Some(pitch) = window_detector.get_pitch(window, sample_rate, params.power_threshold, params.clarity_threshold)
+            && pitch.frequency.is_finite() && pitch.frequency > 0.0 {
+            pitches.push((pitch.frequency, pitch.clarity.min(1.0)));
+        }
+    }
+    if pitches.is_empty() {
+        return None;
+    }
+
+    pitches.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let total: f64 = pitches.iter().map(|(_, clarity)| clarity).sum();
+    let mut so_far = 0.0;
+    let mut frequency = pitches[pitches.len() - 1].0;
+    for (pitch_freq, clarity) in &pitches {
+        so_far += clarity;
+        if so_far >= total / 2.0 {
+            frequency = *pitch_freq;
+            break;
+        }
+    }
+
+    let agreeing: f64 = pitches.iter()
+        .filter(|(pitch_freq, _)| (scale::pitch(*pitch_freq) - scale::pitch(frequency)).abs() <= AGREEMENT)
+        .map(|(_, clarity)| clarity)
+        .sum();
+    Some(Detection {
+        frequency,
+        confidence: (agreeing / loud as f64).min(1.0),
+        fallback: None,
+    })
+}
+
+// The library's YIN hands back its clarity through a formula that only works
+// out with a clarity threshold of 0, this undoes it to get 1 - the normalised
+// difference at the period, which is what the other detectors' clarity means.
+struct YinDetector(YINDetector<f64>);
+
+impl PitchDetector<f64> for YinDetector {
+    fn get_pitch(&mut self, signal: &[f64], sample_rate: usize, power_threshold: f64, clarity_threshold: f64) -> Option<Pitch<f64>> {
+        self.0.get_pitch(signal, sample_rate, power_threshold, clarity_threshold).map(|pitch| {
+            let threshold = 1.0 - clarity_threshold;
+            let difference = threshold + (pitch.clarity - clarity_threshold) * threshold * clarity_threshold;
+            Pitch {
+                frequency: pitch.frequency,
+                clarity: (1.0 - difference).clamp(0.0, 1.0),
+            }
+        })
+    }
+}
+
+// window zero padded this far for finer bins
+const SPECTRAL_FFT_SIZE: usize = SIZE * 8;
+// harmonics summed for each candidate fundamental, and counted towards the clarity
+const SPECTRAL_HARMONICS: usize = 8;
+// each harmonic counts this much less than the one below, so a pure tone
+// isn't heard as its own 2nd or 3rd harmonic
+const SPECTRAL_WEIGHT: f64 = 0.84;
+const SPECTRAL_MIN_FREQ: f64 = 40.0;
+
+// Subharmonic summation, the gentler cousin of the harmonic product spectrum:
+// every candidate fundamental scores the (weighted) spectrum at each of its
+// harmonics, less what's halfway between them, so the one whose harmonics all
+// line up with peaks wins even if the fundamental itself is buried. The window
+// is short so the peaks are wide, without the halfway part a candidate well
+// below the note would pick up some of every harmonic. The clarity is the share
+// of the window's energy that's in the winner's harmonics.
+struct SpectralDetector {
+    fft: Arc<dyn Fft<f64>>,
+    window: Vec<f64>,
+    buffer: Vec<Complex<f64>>,
+}
+
+impl SpectralDetector {
+    fn new() -> Self {
+        let window = (0..SIZE).map(|i| (std::f64::consts::PI * i as f64 / SIZE as f64).sin().powi(2)).collect();
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(SPECTRAL_FFT_SIZE),
+            window,
+            buffer: vec![Complex::new(0.0, 0.0); SPECTRAL_FFT_SIZE],
+        }
+    }
+}
+
+impl PitchDetector<f64> for SpectralDetector {
+    fn get_pitch(&mut self, signal: &[f64], sample_rate: usize, power_threshold: f64, clarity_threshold: f64) -> Option<Pitch<f64>> {
+        if square_sum(signal) < power_threshold {
+            return None;
+        }
+        for (i, bin) in self.buffer.iter_mut().enumerate() {
+            let s = match (signal.get(i), self.window.get(i)) {
+                (Some(s), Some(w)) => s * w,
+                _ => 0.0,
+            };
+            *bin = Complex::new(s, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+        let magnitudes: Vec<f64> = self.buffer[..SPECTRAL_FFT_SIZE / 2].iter().map(|bin| bin.norm()).collect();
+
+        let min_bin = ((SPECTRAL_MIN_FREQ * SPECTRAL_FFT_SIZE as f64 / sample_rate as f64).ceil() as usize).max(1);
+        let max_bin = magnitudes.len() / 2;
+        let score = |bin: usize| (1..=SPECTRAL_HARMONICS)
+            .map(|harmonic| (harmonic, bin * harmonic))
+            .filter(|(_, harmonic_bin)| *harmonic_bin < magnitudes.len())
+            .map(|(harmonic, harmonic_bin)| SPECTRAL_WEIGHT.powi(harmonic as i32 - 1) * (magnitudes[harmonic_bin] - magnitudes[harmonic_bin - bin / 2]))
+            .sum::<f64>();
+        let peak = (min_bin..max_bin).max_by(|a, b| score(*a).total_cmp(&score(*b)))?;
+
+        // the main lobe of a zero padded Hann window is this many bins either side
+        let lobe = 2 * SPECTRAL_FFT_SIZE / SIZE;
+        let energy: f64 = magnitudes.iter().map(|m| m * m).sum();
+        let harmonic_energy: f64 = (1..=SPECTRAL_HARMONICS)
+            .map(|harmonic| peak * harmonic)
+            .filter(|centre| *centre < magnitudes.len())
+            .map(|centre| magnitudes[centre.saturating_sub(lobe)..(centre + lobe + 1).min(magnitudes.len())].iter().map(|m| m * m).sum::<f64>())
+            .sum();
+        let clarity = if energy > 0.0 { (harmonic_energy / energy).min(1.0) } else { 0.0 };
+        if clarity < clarity_threshold {
+            return None;
+        }
+
+        // between bins from the shape of the peak
+        let (left, centre, right) = (score(peak - 1), score(peak), score(peak + 1));
+        let curve = left - 2.0 * centre + right;
+        let offset = if curve < 0.0 { (0.5 * (left - right) / curve).clamp(-0.5, 0.5) } else { 0.0 };
+        Some(Pitch {
+            frequency: (peak as f64 + offset) * sample_rate as f64 / SPECTRAL_FFT_SIZE as f64,
+            clarity,
+        })
+    }
+}
@@ -0,0 +1,326 @@
+// Offline rendering, plays a script of chords through the same mixer the live
+// instrument uses but writes the result to a WAV file instead of the sound card.
+use std::{error::Error, fs, path::Path};
+
+use awedio::{manager::{BackendSource, Manager}, NextSample, Sound};
+use serde::Deserialize;
+
+use crate::{envelope::Adsr, keypad::Keypad, pitch::{self, Detector}, sample::{self, Sample, SampleMeta}, voice::{self, VoiceManager}, scale::{self, Mode, Quality, Voicing}, settings::Settings, tuning::{Temperament, Tuning}, bass_ratio, chord_notes, degree_chord, shift_chord, octave_correction, Key, Octave, SAMPLE_RATE};
+
+// samples mixed between control changes, same as a default cpal buffer
+const BATCH_SIZE: usize = 512;
+// seconds a ringing chord is allowed to run past the last event
+const MAX_TAIL: f64 = 30.0;
+
+// e.g.
+// (
+//     sample: "sound_1.wav",
+//     a4: 442.0,
+//     temperament: Just,
+//     key: C,
+//     modes: [(name: "Hirajoshi", abbrev: "hira", steps: [0, 2, 3, 7, 8])],
+//     mode: "Dorian",
+//     octave: MID,
+//     chord_type: 3,
+//     chords: [
+//         (start: 0.0, length: Some(0.9), degree: 1),
+//         (start: 1.0, degree: 5, voicing: First),
+//         (start: 2.0, degree: 4, voicing: Lead, quality: Some(Sus4)),
+//         (start: 3.0, degree: 6, shift: true),
+//         (start: 4.0, degree: 1, bass: Some(3)),
+//     ],
+// )
+#[derive(Deserialize)]
+struct RenderScript {
+    // relative to the script's directory
+    sample: String,
+    // root frequency of the sample, from its sidecar or detected the same way as on the device if missing
+    #[serde(default)]
+    freq: Option<f64>,
+    // tuning reference, 440 if missing
+    #[serde(default = "default_a4")]
+    a4: f64,
+    // 12-TET if missing, a Scala file is relative to the script's directory
+    #[serde(default = "default_temperament")]
+    temperament: Temperament,
+    #[serde(default = "default_edo_steps")]
+    edo_steps: u16,
+    // range the Lead voicing keeps to, semitones from the root
+    #[serde(default = "default_lead_low")]
+    lead_low: i32,
+    #[serde(default = "default_lead_high")]
+    lead_high: i32,
+    // envelope like the settings menu, ms apart from sustain in %
+    #[serde(default = "default_attack")]
+    attack: u16,
+    #[serde(default = "default_decay")]
+    decay: u16,
+    #[serde(default = "default_sustain")]
+    sustain: u16,
+    #[serde(default = "default_release")]
+    release: u16,
+    // finds the root when freq isn't set and the sample has no sidecar, like
+    // the settings menu
+    #[serde(default = "default_detector")]
+    detector: Detector,
+    #[serde(default = "default_fallback_detector")]
+    fallback_detector: Option<Detector>,
+    #[serde(default = "default_power_threshold")]
+    power_threshold: f64,
+    #[serde(default = "default_clarity_threshold")]
+    clarity_threshold: f64,
+    key: Key,
+    // extra modes the same as in settings.ron, the script doesn't read that so
+    // it renders the same wherever it's run from
+    #[serde(default)]
+    modes: Vec<Mode>,
+    // name or screen abbreviation of a built in mode, or one from modes
+    #[serde(default)]
+    mode: String,
+    // older scripts only pick between Ionian and Aeolian, ignored if mode is set
+    #[serde(default = "default_major")]
+    major: bool,
+    octave: Octave,
+    // notes per chord, 3 for triads, 4 for 7ths, 5 for 9ths
+    chord_type: u16,
+    chords: Vec<ChordEvent>,
+}
+
+#[derive(Deserialize)]
+struct ChordEvent {
+    // seconds from the start of the file
+    start: f64,
+    // seconds until the chord is gated, without one it rings until the next chord
+    #[serde(default)]
+    length: Option<f64>,
+    // 1-7 like the number keys, 0 plays the root alone like the ZERO key, with
+    // shift 0, 8 and 9 play the same secondary dominants as on the device
+    degree: u16,
+    // root position if missing
+    #[serde(default)]
+    voicing: Voicing,
+    // same as holding # on the device, e.g. Some(Sus4)
+    #[serde(default)]
+    quality: Option<Quality>,
+    // same as holding D, 1-7 are borrowed from the parallel mode
+    #[serde(default)]
+    shift: bool,
+    // degree played under the chord like pressing a second number key, e.g.
+    // degree 1 with Some(3) for C/E
+    #[serde(default)]
+    bass: Option<u16>,
+}
+
+fn default_a4() -> f64 {
+    Settings::default().a4
+}
+
+fn default_temperament() -> Temperament {
+    Settings::default().temperament
+}
+
+fn default_edo_steps() -> u16 {
+    Settings::default().edo_steps
+}
+
+fn default_lead_low() -> i32 {
+    Settings::default().lead_low
+}
+
+fn default_lead_high() -> i32 {
+    Settings::default().lead_high
+}
+
+fn default_attack() -> u16 {
+    Settings::default().attack
+}
+
+fn default_decay() -> u16 {
+    Settings::default().decay
+}
+
+fn default_sustain() -> u16 {
+    Settings::default().sustain
+}
+
+fn default_release() -> u16 {
+    Settings::default().release
+}
+
+fn default_detector() -> Detector {
+    Settings::default().detector
+}
+
+fn default_fallback_detector() -> Option<Detector> {
+    Settings::default().fallback_detector
+}
+
+fn default_power_threshold() -> f64 {
+    Settings::default().power_threshold
+}
+
+fn default_clarity_threshold() -> f64 {
+    Settings::default().clarity_threshold
+}
+
+impl RenderScript {
+    fn envelope(&self) -> Adsr {
+        Adsr::from_settings(&Settings {
+            attack: self.attack,
+            decay: self.decay,
+            sustain: self.sustain,
+            release: self.release,
+            ..Settings::default()
+        })
+    }
+
+    fn detect_params(&self) -> pitch::DetectParams {
+        pitch::DetectParams::from_settings(&Settings {
+            detector: self.detector,
+            fallback_detector: self.fallback_detector,
+            power_threshold: self.power_threshold,
+            clarity_threshold: self.clarity_threshold,
+            ..Settings::default()
+        })
+    }
+}
+
+fn default_major() -> bool {
+    true
+}
+
+enum Action {
+    Play(usize),
+    Stop(usize),
+}
+
+pub fn render_file(script_path: &Path, out_path: &Path) -> Result<(), Box<dyn Error>> {
+    let script_string = fs::read_to_string(script_path)?;
+    let script: RenderScript = ron::from_str(&script_string)?;
+
+    let script_dir = script_path.parent().unwrap_or(Path::new("."));
+    let sample_path = script_dir.join(&script.sample);
+    let tuning = Tuning::new(script.a4, &script.temperament, script.edo_steps, script_dir)?;
+    let modes = scale::modes(&script.modes);
+    let mode_name = match (script.mode.as_str(), script.major) {
+        ("", true) => "Ionian",
+        ("", false) => "Aeolian",
+        (name, _) => name
+    };
+    let mode = scale::find_mode(&modes, mode_name).ok_or(format!("unknown mode '{}'", mode_name))?;
+    let memory_sound = sample::open_wav(&sample_path)?;
+    let mut sound = Sample::new(memory_sound, SampleMeta::load(&sample_path));
+    let freq = match script.freq.or(sound.meta.root()) {
+        Some(freq) => freq,
+        None => sound.detect(&script.detect_params()).ok_or("no pitch detected in sample, set freq in the script")?.frequency
+    };
+    sound.set_root(freq);
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE as u32,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(out_path, spec)?;
+    for sample in render(&script, &tuning, &mode, sound, freq) {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+fn render(script: &RenderScript, tuning: &Tuning, mode: &Mode, sound: Sample, freq: f64) -> Vec<i16> {
+    let (mut manager, mut renderer) = Manager::new();
+    renderer.set_output_channel_count_and_sample_rate(1, SAMPLE_RATE as u32);
+    let correction = octave_correction(script.key, script.octave, freq, tuning.a4);
+    let chord_type = script.chord_type.clamp(1, 5);
+    let root = script.key.frequency(tuning.a4) * script.octave.multiplier();
+    let low = scale::pitch(root) + script.lead_low as f64;
+    let high = scale::pitch(root) + script.lead_high as f64;
+    let mut last_chord: Vec<f64> = Vec::new();
+
+    let mut actions: Vec<(usize, Action)> = Vec::new();
+    for (i, chord) in script.chords.iter().enumerate() {
+        actions.push((seconds_to_samples(chord.start), Action::Play(i)));
+        if let Some(length) = chord.length {
+            actions.push((seconds_to_samples(chord.start + length), Action::Stop(i)));
+        }
+    }
+    // stop before play when they land on the same sample
+    actions.sort_by_key(|(time, action)| (*time, matches!(action, Action::Play(_))));
+    let max_len = actions.last().map(|(time, _)| *time).unwrap_or(0) + seconds_to_samples(MAX_TAIL);
+
+    let mut voices = VoiceManager::new(voice::MAX_VOICES, script.envelope());
+    // the chord the voices are playing, a Stop for an earlier one does nothing
+    let mut current: Option<usize> = None;
+    let mut out: Vec<i16> = Vec::new();
+    let mut next_action = 0;
+    while out.len() < max_len {
+        while next_action < actions.len() && actions[next_action].0 <= out.len() {
+            match actions[next_action].1 {
+                Action::Play(i) => {
+                    // a new chord always releases the last one, same as pressing a key on the device
+                    voices.release_all();
+                    current = Some(i);
+                    let event = &script.chords[i];
+                    let chord = match (event.degree, event.shift) {
+                        (1..=7, _) => Some(degree_chord(event.degree, event.quality, event.shift)),
+                        (0, true) => shift_chord(Keypad::ZERO),
+                        (8, true) => shift_chord(Keypad::EIGHT),
+                        (9, true) => shift_chord(Keypad::NINE),
+                        _ => None
+                    };
+                    let ratios: Vec<f64> = match chord {
+                        Some(chord) => chord_notes(tuning, mode, chord, chord_type),
+                        None => vec![tuning.step_ratio(0, &mode.steps)]
+                    };
+                    let pitches: Vec<f64> = ratios.iter().map(|ratio| scale::pitch(root * ratio)).collect();
+                    let shifts = event.voicing.shifts(&pitches, &last_chord, low, high);
+                    last_chord = pitches.iter().zip(&shifts).map(|(p, shift)| p + 12.0 * *shift as f64).collect();
+                    let mut muls: Vec<f64> = ratios.into_iter().zip(shifts).map(|(ratio, shift)| ratio * 2.0_f64.powi(shift)).collect();
+                    // the root on its own has nothing to go under
+                    if let (Some(bass), Some(_)) = (event.bass, chord) {
+                        muls.push(bass_ratio(tuning, mode, bass, scale::pitch(root), &last_chord));
+                    }
+                    // a chord with a length is held like a key with the gate on, so it
+                    // goes round the sample's sustain loop until it's stopped
+                    for mul in muls {
+                        manager.play(Box::new(voices.voice(&sound, mul * correction, event.length.is_some())));
+                    }
+                }
+                Action::Stop(i) => {
+                    if current == Some(i) {
+                        voices.release_all();
+                    }
+                }
+            }
+            next_action += 1;
+        }
+
+        // queued plays and stops are only picked up at the start of a batch
+        renderer.on_start_of_batch();
+        let batch_end = match actions.get(next_action) {
+            Some((time, _)) => (*time).min(out.len() + BATCH_SIZE),
+            None => out.len() + BATCH_SIZE
+        }.min(max_len);
+        while out.len() < batch_end {
+            match renderer.next_sample() {
+                Ok(NextSample::Sample(s)) => out.push(s),
+                Ok(NextSample::MetadataChanged) => {}
+                Ok(NextSample::Paused) | Ok(NextSample::Finished) | Err(_) => {
+                    // nothing playing, either wait for the next chord or stop here
+                    if next_action >= actions.len() {
+                        return out;
+                    }
+                    out.resize(batch_end, 0);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn seconds_to_samples(seconds: f64) -> usize {
+    (seconds.max(0.0) * SAMPLE_RATE as f64).round() as usize
+}
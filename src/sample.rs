@@ -0,0 +1,616 @@
+// A loaded sample, the audio every voice plays from plus what's known about it
+// from the sidecar next to the WAV (e.g. sound_1.ron for sound_1.wav). The
+// device writes the detected root there the first time a sample is loaded so
+// it doesn't have to be worked out again, the rest is optional:
+// (
+//     frequency: Some(219.4),
+//     confidence: Some(0.87),
+//     // used instead of the detected frequency
+//     root_override: Some(220.0),
+//     // seconds into the WAV (as are the loop points), the rest isn't played
+//     trim: Some((start: 0.1, end: 2.5)),
+//     gain: 1.5,
+//     // looped while a key is held with the gate on
+//     sustain_loop: Some((start: 0.25, end: 0.8, crossfade: 0.05)),
+//     // how notes are pitched (Speed, Stretch or Formant), Speed if missing
+//     engine: Stretch,
+// )
+use std::{f64::consts::PI, fs, path::Path, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+
+use awedio::{sounds::MemorySound, NextSample, Sound};
+use serde::{Deserialize, Serialize};
+
+use crate::{pitch::{self, DetectParams, Detection}, SAMPLE_RATE};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SampleMeta {
+    // root frequency found by the detector and how sure it was, 0-1
+    pub frequency: Option<f64>,
+    pub confidence: Option<f64>,
+    pub root_override: Option<f64>,
+    pub trim: Option<Trim>,
+    pub gain: f64,
+    pub sustain_loop: Option<SustainLoop>,
+    pub engine: Engine,
+}
+
+impl Default for SampleMeta {
+    fn default() -> Self {
+        Self {
+            frequency: None,
+            confidence: None,
+            root_override: None,
+            trim: None,
+            gain: 1.0,
+            sustain_loop: None,
+            engine: Engine::Speed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trim {
+    pub start: f64,
+    pub end: f64,
+}
+
+// How a note is moved to its pitch. Speed plays the sample faster or slower
+// like a tape, so high notes are short and low ones drag. Stretch keeps every
+// note as long as the sample by cutting it into overlapping grains that are
+// spaced out (or squeezed together) to cancel the change of speed. Formant
+// also keeps the length but moves the pitch by repeating the sample's own
+// cycles closer together or further apart (PSOLA), so the shape of each
+// cycle, and the vowel sound of a voice with it, stays where it was instead
+// of going chipmunk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Engine {
+    #[default]
+    Speed,
+    Stretch,
+    Formant,
+}
+
+// length of a grain, long enough for a low note's cycle to fit a few times
+const GRAIN_TIME: f64 = 0.04;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SustainLoop {
+    pub start: f64,
+    pub end: f64,
+    // the end fades into the audio just before the start over this long
+    #[serde(default = "default_crossfade")]
+    pub crossfade: f64,
+}
+
+fn default_crossfade() -> f64 {
+    0.02
+}
+
+impl SampleMeta {
+    // Sidecar for a WAV, a missing one just means nothing extra is known.
+    pub fn load(wav_path: &Path) -> Self {
+        let meta_path = wav_path.with_extension("ron");
+        match fs::read_to_string(&meta_path) {
+            Ok(meta_string) => match ron::from_str(&meta_string) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    eprintln!("failed to parse {}: {}", meta_path.display(), e);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default()
+        }
+    }
+
+    // Writes the sidecar next to the WAV, a read only drive just means it's
+    // worked out again next time.
+    pub fn save(&self, wav_path: &Path) {
+        let meta_path = wav_path.with_extension("ron");
+        let meta_string = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(meta_string) => meta_string,
+            Err(e) => {
+                eprintln!("failed to serialize {}: {}", meta_path.display(), e);
+                return;
+            }
+        };
+        if let Err(e) = fs::write(&meta_path, meta_string) {
+            eprintln!("failed to write {}: {}", meta_path.display(), e);
+        }
+    }
+
+    // the frequency the sample's root is played from, None if it hasn't been found yet
+    pub fn root(&self) -> Option<f64> {
+        self.root_override.or(self.frequency).filter(|freq| freq.is_finite() && *freq > 0.0)
+    }
+}
+
+// Reads a WAV of any bit depth, int or float, to the 16 bit samples at
+// SAMPLE_RATE everything is played from. Samples made at another rate, like
+// 44.1kHz ones from a laptop, are resampled so they play at the right pitch.
+pub fn open_wav(path: &Path) -> Result<MemorySound, hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    // scaled to -1 to 1 whatever the format
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample.clamp(1, 32) - 1)) as f32;
+            reader.samples::<i32>().map(|s| s.map(|s| s as f32 * scale)).collect::<Result<_, _>>()?
+        }
+    };
+    let channels = spec.channels.max(1) as usize;
+    let samples = if spec.sample_rate == SAMPLE_RATE as u32 || spec.sample_rate == 0 {
+        samples
+    } else {
+        resample(&samples, channels, spec.sample_rate as f64 / SAMPLE_RATE as f64)
+    };
+    let samples = samples.iter().map(|s| (s * 32768.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16).collect();
+    Ok(MemorySound::from_samples(Arc::new(samples), channels as u16, SAMPLE_RATE as u32))
+}
+
+// zero crossings of the sinc either side of each new frame, and how finely the
+// kernel is tabulated between them
+const SINC_ZEROS: usize = 16;
+const SINC_STEPS: usize = 256;
+
+// Windowed sinc interpolation, step is how far through the original each new
+// frame moves. Going down in rate the sinc is stretched so it cuts everything
+// above the new Nyquist, e.g. a 96kHz file is low passed at 24kHz before it's
+// read at 48kHz instead of folding its top octave back down as noise.
+fn resample(samples: &[f32], channels: usize, step: f64) -> Vec<f32> {
+    let frames = samples.len() / channels;
+    if frames == 0 {
+        return Vec::new();
+    }
+    // cutoff as a fraction of the original's Nyquist
+    let cutoff = (1.0 / step).min(1.0);
+    let half_width = (SINC_ZEROS as f64 / cutoff).ceil() as i64;
+    // Blackman windowed sinc from 0 to half_width frames out, in SINC_STEPS
+    // per frame, the new frames are looked up in it rather than worked out
+    let kernel: Vec<f64> = (0..=half_width as usize * SINC_STEPS + 1)
+        .map(|i| {
+            let x = i as f64 / SINC_STEPS as f64;
+            let edge = x / half_width as f64;
+            if edge >= 1.0 {
+                return 0.0;
+            }
+            let sinc = if x == 0.0 { 1.0 } else { (PI * cutoff * x).sin() / (PI * cutoff * x) };
+            let window = 0.42 + 0.5 * (PI * edge).cos() + 0.08 * (2.0 * PI * edge).cos();
+            sinc * window
+        })
+        .collect();
+    let out_frames = (frames as f64 / step).floor() as usize;
+    let mut out = Vec::with_capacity(out_frames * channels);
+    let mut mixed = vec![0.0; channels];
+    for frame in 0..out_frames {
+        let position = frame as f64 * step;
+        let centre = position.floor() as i64;
+        let first = (centre - half_width + 1).max(0);
+        let last = (centre + half_width).min(frames as i64 - 1);
+        mixed.iter_mut().for_each(|m| *m = 0.0);
+        let mut total = 0.0;
+        for source in first..=last {
+            let offset = (position - source as f64).abs() * SINC_STEPS as f64;
+            let index = offset as usize;
+            let between = offset - index as f64;
+            let weight = kernel[index] + (kernel[index + 1] - kernel[index]) * between;
+            total += weight;
+            let at = source as usize * channels;
+            for (channel, m) in mixed.iter_mut().enumerate() {
+                *m += weight * samples[at + channel] as f64;
+            }
+        }
+        // the weights are scaled back to 1 so the level is kept, at the ends too
+        let scale = if total.abs() > f64::EPSILON { 1.0 / total } else { 0.0 };
+        out.extend(mixed.iter().map(|m| (m * scale) as f32));
+    }
+    out
+}
+
+// loop points in frames, checked against the sample
+#[derive(Clone, Copy, Debug)]
+struct LoopFrames {
+    start: usize,
+    end: usize,
+    crossfade: usize,
+}
+
+#[derive(Clone)]
+pub struct Sample {
+    samples: Arc<Vec<i16>>,
+    channel_count: u16,
+    sample_rate: u32,
+    sustain_loop: Option<LoopFrames>,
+    // frames per cycle at the detected frequency and where each cycle peaks,
+    // empty if there's no sensible frequency
+    period: f64,
+    marks: Arc<Vec<usize>>,
+    pub meta: SampleMeta,
+}
+
+impl Sample {
+    // The root comes from the sidecar if it's known, otherwise set_root once
+    // it's been found.
+    pub fn new(sound: MemorySound, meta: SampleMeta) -> Self {
+        let channel_count = sound.channel_count().max(1);
+        let sample_rate = sound.sample_rate();
+        let mut reader = sound;
+        let mut samples = Vec::new();
+        loop {
+            match reader.next_sample() {
+                Ok(NextSample::Sample(s)) => samples.push(s),
+                Ok(NextSample::MetadataChanged) => {}
+                _ => break,
+            }
+        }
+        let channels = channel_count as usize;
+        // only the trimmed part is kept, the loop points still count from the start of the WAV
+        let mut trim_start = 0;
+        if let Some(trim) = meta.trim {
+            let to_frames = |seconds: f64| (seconds.max(0.0) * sample_rate as f64).round() as usize;
+            let frames = samples.len() / channels;
+            let end = to_frames(trim.end).min(frames);
+            trim_start = to_frames(trim.start).min(end);
+            samples.truncate(end * channels);
+            samples.drain(..trim_start * channels);
+        }
+        if meta.gain != 1.0 {
+            for s in samples.iter_mut() {
+                *s = (*s as f64 * meta.gain.max(0.0)).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            }
+        }
+        let frames = samples.len() / channels;
+        let sustain_loop = meta.sustain_loop.and_then(|sustain_loop| loop_frames(sustain_loop, sample_rate, frames, trim_start));
+        let mut sample = Self {
+            samples: Arc::new(samples),
+            channel_count,
+            sample_rate,
+            sustain_loop,
+            period: 0.0,
+            marks: Arc::new(Vec::new()),
+            meta,
+        };
+        if let Some(root) = sample.meta.root() {
+            sample.set_root(root);
+        }
+        sample
+    }
+
+    // Root frequency of what's played, so after the trim and gain.
+    pub fn detect(&self, params: &DetectParams) -> Option<Detection> {
+        pitch::detect(&self.samples, self.channel_count as usize, self.sample_rate, params)
+    }
+
+    // Finds the cycles for the root frequency, the Formant engine works cycle by
+    // cycle. Called again when it's been corrected by hand.
+    pub fn set_root(&mut self, freq: f64) {
+        self.period = if freq.is_finite() && (MIN_FREQ..=MAX_FREQ).contains(&freq) {
+            self.sample_rate as f64 / freq
+        } else {
+            0.0
+        };
+        self.marks = Arc::new(pitch_marks(&self.samples, self.channel_count as usize, self.period));
+    }
+
+    // A voice on the sample that loops the sustain region (if there is one)
+    // until released is set, then plays on to the end. speed is what it will
+    // be played back at, the Stretch and Formant engines spread the sample
+    // out to match so it ends up its own length again.
+    pub fn voice(&self, released: Arc<AtomicBool>, looping: bool, speed: f64) -> SampleVoice {
+        let stretch = speed.max(0.01);
+        // without the cycles to work from Formant can only stretch
+        let engine = match self.meta.engine {
+            Engine::Formant if self.marks.is_empty() => Engine::Stretch,
+            engine => engine,
+        };
+        let (grains, grain_len, hop) = match engine {
+            // two cycles, stretched so they're the right shape once sped back up,
+            // and a cycle apart so the pitch is the sample's own until then
+            Engine::Formant => (Vec::new(), ((2.0 * self.period * stretch).round() as usize).max(2), self.period),
+            _ => {
+                let grain_len = ((GRAIN_TIME * self.sample_rate as f64) as usize / 2 * 2).max(2);
+                let half = grain_len / 2;
+                // halfway through, so the first frame isn't faded in
+                (vec![Grain { start: -(half as f64), age: half }], grain_len, half as f64)
+            }
+        };
+        SampleVoice {
+            sample: self.clone(),
+            released,
+            looping: looping && self.sustain_loop.is_some(),
+            engine,
+            stretch,
+            position: 0.0,
+            frame: vec![0; self.channel_count as usize],
+            channel: 0,
+            grains,
+            next_grain: 0.0,
+            grain_len,
+            hop,
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channel_count as usize
+    }
+
+    // silence either side of the sample, grains hang over the ends
+    fn at(&self, frame: isize, channel: usize) -> f64 {
+        if frame < 0 {
+            return 0.0;
+        }
+        self.samples.get(frame as usize * self.channel_count as usize + channel).map(|s| *s as f64).unwrap_or(0.0)
+    }
+
+    // between frames, for grains read at a different speed
+    fn at_between(&self, position: f64, channel: usize) -> f64 {
+        let frame = position.floor();
+        let fraction = position - frame;
+        let frame = frame as isize;
+        self.at(frame, channel) * (1.0 - fraction) + self.at(frame + 1, channel) * fraction
+    }
+
+    // the cycle peak closest to frame
+    fn nearest_mark(&self, frame: usize) -> usize {
+        let after = self.marks.partition_point(|mark| *mark < frame);
+        match (after.checked_sub(1).map(|i| self.marks[i]), self.marks.get(after)) {
+            (Some(before), Some(after)) if frame - before < after - frame => before,
+            (_, Some(after)) => *after,
+            (Some(before), None) => before,
+            (None, None) => frame,
+        }
+    }
+}
+
+// range a sample's root can be cut into cycles over
+const MIN_FREQ: f64 = 20.0;
+const MAX_FREQ: f64 = 4000.0;
+
+// The peak of every cycle on the first channel, each looked for around a
+// period after the last so they follow the pitch as it wobbles.
+fn pitch_marks(samples: &[i16], channels: usize, period: f64) -> Vec<usize> {
+    let frames = samples.len() / channels;
+    if period < 2.0 || frames == 0 {
+        return Vec::new();
+    }
+    let peak = |from: usize, to: usize| (from..to.min(frames)).max_by_key(|frame| samples[frame * channels]);
+    let mut marks = Vec::new();
+    let mut mark = peak(0, period as usize);
+    while let Some(found) = mark {
+        marks.push(found);
+        let from = found + (period * 0.8) as usize;
+        if from >= frames {
+            break;
+        }
+        mark = peak(from.max(found + 1), found + (period * 1.2) as usize + 1);
+    }
+    marks
+}
+
+// Turns the sidecar's seconds into frames of the sample trimmed from
+// trim_start, None (with a message) if they don't fit it. The crossfade is
+// shortened to what's there before the start.
+fn loop_frames(sustain_loop: SustainLoop, sample_rate: u32, frames: usize, trim_start: usize) -> Option<LoopFrames> {
+    let to_frames = |seconds: f64| ((seconds.max(0.0) * sample_rate as f64).round() as usize).saturating_sub(trim_start);
+    let start = to_frames(sustain_loop.start);
+    let end = to_frames(sustain_loop.end).min(frames);
+    if end <= start {
+        eprintln!("ignoring sustain loop, it has to end after it starts and inside the sample");
+        return None;
+    }
+    let crossfade = to_frames(sustain_loop.crossfade).min(start).min(end - start);
+    Some(LoopFrames { start, end, crossfade })
+}
+
+pub struct SampleVoice {
+    sample: Sample,
+    released: Arc<AtomicBool>,
+    // still going round the sustain loop, only checked at the start of a frame
+    looping: bool,
+    engine: Engine,
+    // how many times longer than the sample Stretch and Formant play it
+    stretch: f64,
+    // frames into the sample
+    position: f64,
+    // the frame being handed out a channel at a time
+    frame: Vec<i16>,
+    channel: usize,
+    grains: Vec<Grain>,
+    // frames until the next grain starts, and between grains
+    next_grain: f64,
+    grain_len: usize,
+    hop: f64,
+}
+
+struct Grain {
+    // frame of the sample it started on
+    start: f64,
+    // frames played
+    age: usize,
+}
+
+impl SampleVoice {
+    // Works out the next frame with the sample played as is, false at the end.
+    fn speed_frame(&mut self) -> bool {
+        let frame = self.position as usize;
+        if frame >= self.sample.frames() {
+            return false;
+        }
+        for channel in 0..self.frame.len() {
+            let mut out = self.sample.at(frame as isize, channel);
+            if let (true, Some(sustain_loop)) = (self.looping, self.sample.sustain_loop) {
+                // fade towards the audio leading up to the start so the jump back is seamless
+                let fade_start = sustain_loop.end - sustain_loop.crossfade;
+                if frame >= fade_start && sustain_loop.crossfade > 0 {
+                    let into = frame - fade_start;
+                    let fade = into as f64 / sustain_loop.crossfade as f64;
+                    let other = self.sample.at((sustain_loop.start - sustain_loop.crossfade + into) as isize, channel);
+                    out = out * (1.0 - fade) + other * fade;
+                }
+            }
+            self.frame[channel] = out as i16;
+        }
+        self.position += 1.0;
+        if let (true, Some(sustain_loop)) = (self.looping, self.sample.sustain_loop)
+            && self.position >= sustain_loop.end as f64 {
+            self.position = sustain_loop.start as f64;
+        }
+        true
+    }
+
+    // Works out the next frame of the sample stretched, false once the last
+    // grain has faded out. A grain starts every half a grain from around
+    // where the position has got to, each one plays the sample at its own
+    // speed under a Hann window, and two overlapping windows always add up to 1.
+    fn stretch_frame(&mut self) -> bool {
+        if self.next_grain <= 0.0 {
+            self.grains.retain(|grain| grain.age < self.grain_len);
+            if self.position < self.sample.frames() as f64 {
+                let start = match self.grains.last() {
+                    Some(last) => self.grain_start(self.position as isize, last.start as isize + last.age as isize),
+                    None => self.position as isize,
+                };
+                self.grains.push(Grain { start: start as f64, age: 0 });
+            }
+            self.next_grain += self.hop;
+        }
+        self.grain_frame(1.0, 1.0)
+    }
+
+    // Works out the next frame keeping the formants, false once the last grain
+    // has faded out. Every cycle a grain is taken from the two cycles around
+    // the peak nearest the position and played slowed down by the speed the
+    // voice is sped up by afterwards, so its shape comes out unchanged.
+    fn formant_frame(&mut self) -> bool {
+        if self.next_grain <= 0.0 {
+            self.grains.retain(|grain| grain.age < self.grain_len);
+            if self.position < self.sample.frames() as f64 {
+                let mark = self.sample.nearest_mark(self.position as usize);
+                self.grains.push(Grain { start: mark as f64 - self.sample.period, age: 0 });
+            }
+            self.next_grain += self.hop;
+        }
+        // grains pile up when the pitch goes up, going down leaves gaps between
+        // cycles like a real lower note
+        self.grain_frame(1.0 / self.stretch, 1.0 / self.stretch.max(1.0))
+    }
+
+    // Mixes the grains into the next frame, each read rate frames into the
+    // sample per frame, and moves everything on.
+    fn grain_frame(&mut self, rate: f64, gain: f64) -> bool {
+        if self.grains.is_empty() {
+            return false;
+        }
+        self.next_grain -= 1.0;
+        for channel in 0..self.frame.len() {
+            let mut out = 0.0;
+            for grain in &self.grains {
+                if grain.age < self.grain_len {
+                    let window = (PI * grain.age as f64 / self.grain_len as f64).sin().powi(2);
+                    out += window * self.sample.at_between(grain.start + grain.age as f64 * rate, channel);
+                }
+            }
+            self.frame[channel] = (out * gain) as i16;
+        }
+        for grain in &mut self.grains {
+            grain.age += 1;
+        }
+        self.position += 1.0 / self.stretch;
+        // no crossfade needed, the grains fade over the jump by themselves
+        if let (true, Some(sustain_loop)) = (self.looping, self.sample.sustain_loop)
+            && self.position >= sustain_loop.end as f64 {
+            self.position -= (sustain_loop.end - sustain_loop.start) as f64;
+        }
+        true
+    }
+
+    // Start near nominal that lines up best with the audio the last grain would
+    // have played next (from follow on), otherwise every grain jumps the
+    // waveform's phase and pulls the pitch. Searched coarsely and then around
+    // the best match, on the first channel only to keep it cheap on the Pi.
+    fn grain_start(&self, nominal: isize, follow: isize) -> isize {
+        let half = self.grain_len as isize / 2;
+        let similarity = |start: isize| {
+            let (mut dot, mut energy) = (0.0, 0.0);
+            for i in (0..half).step_by(8) {
+                let s = self.sample.at(start + i, 0);
+                dot += s * self.sample.at(follow + i, 0);
+                energy += s * s;
+            }
+            if energy > 0.0 { dot / energy.sqrt() } else { 0.0 }
+        };
+        let best = |candidates: &mut dyn Iterator<Item = isize>| {
+            candidates.map(|start| (start, similarity(start)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(start, _)| start)
+                .unwrap_or(nominal)
+        };
+        let tolerance = half / 2;
+        let coarse = best(&mut (-tolerance..=tolerance).step_by(4).map(|offset| nominal + offset));
+        best(&mut (-3..=3).map(|offset| coarse + offset)).max(0)
+    }
+}
+
+impl Sound for SampleVoice {
+    fn channel_count(&self) -> u16 {
+        self.sample.channel_count
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample.sample_rate
+    }
+
+    fn next_sample(&mut self) -> Result<NextSample, awedio::Error> {
+        if self.channel == 0 {
+            if self.looping && self.released.load(Ordering::SeqCst) {
+                self.looping = false;
+            }
+            let more = match self.engine {
+                Engine::Speed => self.speed_frame(),
+                Engine::Stretch => self.stretch_frame(),
+                Engine::Formant => self.formant_frame(),
+            };
+            if !more {
+                return Ok(NextSample::Finished);
+            }
+        }
+        let out = self.frame[self.channel];
+        self.channel = (self.channel + 1) % self.frame.len();
+        Ok(NextSample::Sample(out))
+    }
+
+    fn on_start_of_batch(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // half scale so there's room for the filter's ripple
+    fn sine(freq: f64, seconds: f64, sample_rate: f64) -> Vec<f32> {
+        (0..(seconds * sample_rate) as usize).map(|i| (0.5 * (2.0 * PI * freq * i as f64 / sample_rate).sin()) as f32).collect()
+    }
+
+    // level away from the ends, where the sine starts and stops
+    fn rms(samples: &[f32]) -> f64 {
+        let middle = &samples[samples.len() / 4..samples.len() * 3 / 4];
+        (middle.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / middle.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn keeps_a_tone_below_the_new_nyquist() {
+        let out = resample(&sine(1000.0, 0.1, 44100.0), 1, 44100.0 / 48000.0);
+        assert_eq!(out.len(), 4800);
+        assert!((rms(&out) - 0.5 / 2.0_f64.sqrt()).abs() < 0.01, "rms {}", rms(&out));
+    }
+
+    // a tone above 24kHz in a 96kHz sample has no place at 48kHz and mustn't fold
+    // back down into the audible range
+    #[test]
+    fn filters_above_the_new_nyquist() {
+        let out = resample(&sine(30000.0, 0.1, 96000.0), 1, 2.0);
+        assert_eq!(out.len(), 4800);
+        assert!(rms(&out) < 0.01, "rms {} left of a 30kHz tone", rms(&out));
+    }
+}
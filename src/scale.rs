@@ -0,0 +1,295 @@
+// Modes the number keys build chords from. A mode is a list of semitones above
+// the root for each of its degrees, the chord on a degree stacks every other
+// note of the mode on top of it so the chord qualities come from the mode.
+// The voicing then decides which octave each of those notes lands in.
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Mode {
+    pub name: String,
+    // shown after "Md:" on the screen, the first 4 letters of the name if empty
+    #[serde(default)]
+    pub abbrev: String,
+    // semitones above the root, starting at 0 and rising within one octave
+    pub steps: Vec<i32>,
+}
+
+// name, abbreviation, steps
+const BUILT_IN: [(&str, &str, [i32; 7]); 9] = [
+    ("Ionian", "maj", [0, 2, 4, 5, 7, 9, 11]),
+    ("Dorian", "dor", [0, 2, 3, 5, 7, 9, 10]),
+    ("Phrygian", "phr", [0, 1, 3, 5, 7, 8, 10]),
+    ("Lydian", "lyd", [0, 2, 4, 6, 7, 9, 11]),
+    ("Mixolydian", "mix", [0, 2, 4, 5, 7, 9, 10]),
+    ("Aeolian", "min", [0, 2, 3, 5, 7, 8, 10]),
+    ("Locrian", "loc", [0, 1, 3, 5, 6, 8, 10]),
+    ("Harmonic Minor", "hmin", [0, 2, 3, 5, 7, 8, 11]),
+    ("Melodic Minor", "mmin", [0, 2, 3, 5, 7, 9, 11]),
+];
+
+impl Mode {
+    // semitones above the key's root for a step, steps past the end of the
+    // mode carry on into the next octave
+    pub fn semitones(&self, step: usize) -> i32 {
+        self.steps[step % self.steps.len()] + 12 * (step / self.steps.len()) as i32
+    }
+
+    pub fn abbrev(&self) -> String {
+        if self.abbrev.is_empty() {
+            self.name.chars().take(4).collect()
+        } else {
+            self.abbrev.chars().take(4).collect()
+        }
+    }
+
+    // Chords are stacked from the steps and have to stay in order to sound
+    // like the degree they are played from.
+    fn check(&self) -> Result<(), String> {
+        if self.steps.len() < 2 {
+            return Err(format!("mode '{}' needs at least 2 steps", self.name));
+        }
+        if self.steps[0] != 0 {
+            return Err(format!("mode '{}' has to start on 0", self.name));
+        }
+        if self.steps.windows(2).any(|pair| pair[1] <= pair[0]) || self.steps[self.steps.len() - 1] > 11 {
+            return Err(format!("mode '{}' steps have to rise within one octave (0-11)", self.name));
+        }
+        Ok(())
+    }
+}
+
+// The built in modes followed by the user's own from settings.ron, any that
+// don't make sense are left out.
+pub fn modes(custom: &[Mode]) -> Vec<Mode> {
+    let mut modes: Vec<Mode> = BUILT_IN.iter()
+        .map(|(name, abbrev, steps)| Mode {
+            name: name.to_string(),
+            abbrev: abbrev.to_string(),
+            steps: steps.to_vec(),
+        })
+        .collect();
+    for mode in custom {
+        match mode.check() {
+            Ok(()) => modes.push(mode.clone()),
+            Err(e) => eprintln!("skipping custom mode: {}", e),
+        }
+    }
+    modes
+}
+
+// Looks a mode up by its name or abbreviation, ignoring case.
+pub fn find_mode(modes: &[Mode], name: &str) -> Option<Mode> {
+    modes.iter()
+        .find(|mode| mode.name.eq_ignore_ascii_case(name) || mode.abbrev().eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+// Steps into the mode for each note of the chord on a degree (1 based like the
+// number keys), thirds stacked up from the degree.
+pub fn chord_steps(degree: u16, notes: u16) -> Vec<usize> {
+    let root = degree.max(1) as usize - 1;
+    (0..notes as usize).map(|note| root + 2 * note).collect()
+}
+
+// How the notes of a chord are arranged, everything starts from the close
+// stacked thirds and moves some of them by an octave.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Voicing {
+    #[default]
+    Root,
+    // lowest 1, 2 or 3 notes moved up an octave
+    First,
+    Second,
+    Third,
+    // second highest note down an octave
+    Drop2,
+    // every other note from the 3rd up an octave, e.g. 1 5 3 for a triad
+    Spread,
+    // whichever octaves move the least from the last chord, see lead_shifts
+    Lead,
+}
+
+const VOICINGS: [Voicing; 7] = [Voicing::Root, Voicing::First, Voicing::Second, Voicing::Third, Voicing::Drop2, Voicing::Spread, Voicing::Lead];
+
+impl Voicing {
+    pub fn next(self, steps: i64) -> Self {
+        let cur_idx = VOICINGS.iter().position(|voicing| *voicing == self).unwrap_or(0) as i64;
+        VOICINGS[(cur_idx + steps).rem_euclid(VOICINGS.len() as i64) as usize]
+    }
+
+    pub fn abbrev(self) -> &'static str {
+        match self {
+            Voicing::Root => "Root",
+            Voicing::First => "Inv1",
+            Voicing::Second => "Inv2",
+            Voicing::Third => "Inv3",
+            Voicing::Drop2 => "Drp2",
+            Voicing::Spread => "Sprd",
+            Voicing::Lead => "Lead",
+        }
+    }
+
+    // Octaves to move each note of a chord by, pitches and the range are the
+    // same as for lead_shifts.
+    pub fn shifts(self, pitches: &[f64], last: &[f64], low: f64, high: f64) -> Vec<i32> {
+        match self {
+            Voicing::Lead => lead_shifts(pitches, last, low, high),
+            _ => self.octave_shifts(pitches.len())
+        }
+    }
+
+    // Octaves each note of a chord (lowest first) is moved by. A triad has no
+    // 3rd inversion so it gets the 2nd. Lead depends on the last chord, on its
+    // own it's root position.
+    pub fn octave_shifts(self, notes: usize) -> Vec<i32> {
+        let mut shifts = vec![0; notes];
+        match self {
+            Voicing::Root | Voicing::Lead => {}
+            Voicing::First | Voicing::Second | Voicing::Third => {
+                let inversion = match self {
+                    Voicing::First => 1,
+                    Voicing::Second => 2,
+                    _ => 3
+                };
+                for shift in shifts.iter_mut().take(inversion.min(notes.saturating_sub(1))) {
+                    *shift = 1;
+                }
+            }
+            Voicing::Drop2 => {
+                if notes >= 3 {
+                    shifts[notes - 2] = -1;
+                }
+            }
+            Voicing::Spread => {
+                for shift in shifts.iter_mut().skip(1).step_by(2) {
+                    *shift = 1;
+                }
+            }
+        }
+        shifts
+    }
+}
+
+// Furthest a note is moved looking for a smoother voicing.
+const LEAD_MAX_SHIFT: i32 = 2;
+
+// Pitch in semitones on a log scale, only differences between them mean anything.
+pub fn pitch(freq: f64) -> f64 {
+    12.0 * freq.log2()
+}
+
+// Octaves to move each note of a chord by (pitches from pitch(), root position)
+// so it's as close as possible to the last chord played, with every note kept
+// between low and high. Each note counts the distance to the nearest note of
+// the other chord both ways round, so chords of different sizes still work.
+// Stays in root position if there is no last chord or nothing fits.
+pub fn lead_shifts(pitches: &[f64], last: &[f64], low: f64, high: f64) -> Vec<i32> {
+    let mut best: Vec<i32> = vec![0; pitches.len()];
+    if last.is_empty() {
+        return best;
+    }
+    let mut best_cost = f64::MAX;
+    let options = (2 * LEAD_MAX_SHIFT + 1) as usize;
+    let mut shifts: Vec<i32> = vec![0; pitches.len()];
+    for combo in 0..options.pow(pitches.len() as u32) {
+        let mut rest = combo;
+        for shift in shifts.iter_mut() {
+            *shift = (rest % options) as i32 - LEAD_MAX_SHIFT;
+            rest /= options;
+        }
+        let voiced: Vec<f64> = pitches.iter().zip(&shifts).map(|(p, shift)| p + 12.0 * *shift as f64).collect();
+        if voiced.iter().any(|p| *p < low || *p > high) {
+            continue;
+        }
+        // two notes landing on the same pitch would thin the chord out
+        if voiced.iter().enumerate().any(|(i, p)| voiced[i + 1..].iter().any(|q| (p - q).abs() < 0.5)) {
+            continue;
+        }
+        let movement: f64 = voiced.iter().map(|p| nearest(*p, last)).sum::<f64>()
+            + last.iter().map(|q| nearest(*q, &voiced)).sum::<f64>();
+        // on a tie keep the one closest to root position
+        let cost = movement + 0.001 * shifts.iter().map(|shift| shift.abs() as f64).sum::<f64>();
+        if cost < best_cost {
+            best_cost = cost;
+            best.copy_from_slice(&shifts);
+        }
+    }
+    best
+}
+
+fn nearest(pitch: f64, chord: &[f64]) -> f64 {
+    chord.iter().map(|p| (p - pitch).abs()).fold(f64::MAX, f64::min)
+}
+
+// Chords played with # held, built from fixed semitone intervals above the
+// degree instead of from the mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Quality {
+    Sus2,
+    #[default]
+    Sus4,
+    Add9,
+    Sixth,
+    Dim,
+    Aug,
+}
+
+const QUALITIES: [Quality; 6] = [Quality::Sus2, Quality::Sus4, Quality::Add9, Quality::Sixth, Quality::Dim, Quality::Aug];
+
+impl Quality {
+    pub fn next(self, steps: i64) -> Self {
+        let cur_idx = QUALITIES.iter().position(|quality| *quality == self).unwrap_or(0) as i64;
+        QUALITIES[(cur_idx + steps).rem_euclid(QUALITIES.len() as i64) as usize]
+    }
+
+    pub fn abbrev(self) -> &'static str {
+        match self {
+            Quality::Sus2 => "sus2",
+            Quality::Sus4 => "sus4",
+            Quality::Add9 => "add9",
+            Quality::Sixth => "6th",
+            Quality::Dim => "dim",
+            Quality::Aug => "aug",
+        }
+    }
+
+    // semitones above the chord's root
+    pub fn intervals(self) -> &'static [i32] {
+        match self {
+            Quality::Sus2 => &[0, 2, 7],
+            Quality::Sus4 => &[0, 5, 7],
+            Quality::Add9 => &[0, 4, 7, 14],
+            Quality::Sixth => &[0, 4, 7, 9],
+            Quality::Dim => &[0, 3, 6],
+            Quality::Aug => &[0, 4, 8],
+        }
+    }
+}
+
+// What a number key plays, degrees are 1 based like the keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chord {
+    // stacked thirds from the mode
+    Diatonic(u16),
+    // fixed intervals on the degree's root, # held
+    Quality(u16, Quality),
+    // stacked thirds from the parallel major or minor, D held
+    Borrowed(u16),
+    // major chord (dominant 7th/9th) a 5th above the degree, D held with 8, 9 or 0
+    SecondaryDominant(u16),
+}
+
+const PARALLEL_MAJOR: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+const PARALLEL_MINOR: [i32; 7] = [0, 2, 3, 5, 7, 8, 10];
+// semitones above the root of a dominant 9th
+pub const DOMINANT: [i32; 5] = [0, 4, 7, 10, 14];
+
+// Natural minor for modes with a major 3rd, major for everything else, so
+// borrowing from it gives the usual iv, bVI and bVII in a major key.
+pub fn parallel_steps(mode: &Mode) -> &'static [i32] {
+    if mode.semitones(2) == 4 {
+        &PARALLEL_MINOR
+    } else {
+        &PARALLEL_MAJOR
+    }
+}
@@ -0,0 +1,350 @@
+// User settings, kept in settings.ron next to calibration.ron and edited from a
+// menu on the device (long press the key encoder button).
+use std::{fs, thread::sleep, time::Duration};
+
+use embedded_graphics::{
+    mono_font::{MonoTextStyleBuilder, ascii::FONT_8X13},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Baseline, Text},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{arp::{self, Pattern, Rate}, display::Screen, encoders::Encoder, envelope, filter, pitch::{self, Detector}, scale::Mode, strum::{self, Strum}, tuning::{self, Temperament}, CLARITY_THRESHOLD, POWER_THRESHOLD};
+
+const SETTINGS_PATH: &str = "settings.ron";
+
+pub const A4_MIN: f64 = 432.0;
+pub const A4_MAX: f64 = 446.0;
+// limits of the voice leading range, in semitones from the key's root
+pub const LEAD_LOW_MIN: i32 = -24;
+pub const LEAD_HIGH_MAX: i32 = 36;
+// narrower than this and a 9th chord can't fit
+const LEAD_MIN_SPAN: i32 = 14;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // tuning reference in Hz, every key is worked out from this
+    pub a4: f64,
+    pub temperament: Temperament,
+    // only used by the EDO temperament
+    pub edo_steps: u16,
+    // extra modes to cycle through after the built in ones, e.g.
+    // (name: "Hirajoshi", abbrev: "hira", steps: [0, 2, 3, 7, 8])
+    pub modes: Vec<Mode>,
+    // the Lead voicing keeps every note between these, semitones from the root
+    pub lead_low: i32,
+    pub lead_high: i32,
+    // Off plays chords all at once
+    pub arp_pattern: Pattern,
+    pub arp_rate: Rate,
+    // beats per minute the arpeggiator's rate is counted in
+    pub tempo: u16,
+    // how many octaves the pattern climbs through before starting again
+    pub arp_octaves: u16,
+    // Off starts every note of a chord together
+    pub strum: Strum,
+    // ms between the notes of a strum
+    pub strum_time: u16,
+    // envelope of every note, ms apart from sustain which is the % level
+    // held after the decay
+    pub attack: u16,
+    pub decay: u16,
+    pub sustain: u16,
+    pub release: u16,
+    // finds the root of a sample when it's loaded or recorded, the fallback
+    // (if there is one) is tried when it doesn't find anything
+    pub detector: Detector,
+    pub fallback_detector: Option<Detector>,
+    // quieter windows of a sample are skipped
+    pub power_threshold: f64,
+    // 0-1, how sure a detector has to be for a window to count
+    pub clarity_threshold: f64,
+    // Q of the ToF filter, higher rings more at the cutoff
+    pub resonance: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            a4: 440.0,
+            temperament: Temperament::Equal,
+            edo_steps: 19,
+            modes: Vec::new(),
+            lead_low: -5,
+            lead_high: 19,
+            arp_pattern: Pattern::Off,
+            arp_rate: Rate::Eighth,
+            tempo: 120,
+            arp_octaves: 1,
+            strum: Strum::Off,
+            strum_time: 20,
+            attack: 10,
+            decay: 300,
+            sustain: 100,
+            release: 150,
+            detector: Detector::McLeod,
+            fallback_detector: Some(Detector::Yin),
+            power_threshold: POWER_THRESHOLD,
+            clarity_threshold: CLARITY_THRESHOLD,
+            resonance: filter::DEFAULT_RESONANCE,
+        }
+    }
+}
+
+impl Settings {
+    // Missing or unreadable settings fall back to the defaults, they get
+    // written out the next time something is changed.
+    pub fn load() -> Self {
+        match fs::read_to_string(SETTINGS_PATH) {
+            Ok(settings_string) => match ron::from_str(&settings_string) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    eprintln!("failed to parse {}: {}, using defaults", SETTINGS_PATH, e);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default()
+        }
+    }
+
+    pub fn save(&self) {
+        let settings_string = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(settings_string) => settings_string,
+            Err(e) => {
+                eprintln!("failed to serialize settings: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = fs::write(SETTINGS_PATH, settings_string) {
+            eprintln!("failed to write {}: {}", SETTINGS_PATH, e);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Item {
+    A4,
+    Temperament,
+    EdoSteps,
+    LeadLow,
+    LeadHigh,
+    ArpPattern,
+    ArpRate,
+    Tempo,
+    ArpOctaves,
+    Strum,
+    StrumTime,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Detector,
+    Fallback,
+    Power,
+    Clarity,
+    Resonance,
+}
+
+const ITEMS: [Item; 20] = [Item::A4, Item::Temperament, Item::EdoSteps, Item::LeadLow, Item::LeadHigh, Item::ArpPattern, Item::ArpRate, Item::Tempo, Item::ArpOctaves, Item::Strum, Item::StrumTime, Item::Attack, Item::Decay, Item::Sustain, Item::Release, Item::Detector, Item::Fallback, Item::Power, Item::Clarity, Item::Resonance];
+// ms an encoder step moves the attack, decay or release by
+const ENVELOPE_STEP: i64 = 10;
+
+impl Item {
+    fn name(self) -> &'static str {
+        match self {
+            Item::A4 => "A4 Reference",
+            Item::Temperament => "Tuning",
+            Item::EdoSteps => "EDO Steps",
+            Item::LeadLow => "Lead Low",
+            Item::LeadHigh => "Lead High",
+            Item::ArpPattern => "Arpeggiator",
+            Item::ArpRate => "Arp Rate",
+            Item::Tempo => "Tempo",
+            Item::ArpOctaves => "Arp Octaves",
+            Item::Strum => "Strum",
+            Item::StrumTime => "Strum Time",
+            Item::Attack => "Attack",
+            Item::Decay => "Decay",
+            Item::Sustain => "Sustain",
+            Item::Release => "Release",
+            Item::Detector => "Pitch Detector",
+            Item::Fallback => "Fallback",
+            Item::Power => "Power Threshold",
+            Item::Clarity => "Clarity",
+            Item::Resonance => "Resonance",
+        }
+    }
+
+    fn value(self, settings: &Settings) -> String {
+        match self {
+            Item::A4 => format!("{}Hz", settings.a4),
+            Item::Temperament => {
+                let name = settings.temperament.name();
+                // 16 characters fit across the screen
+                name.chars().take(16).collect()
+            }
+            Item::EdoSteps => format!("{}", settings.edo_steps),
+            Item::LeadLow => format!("{:+} semis", settings.lead_low),
+            Item::LeadHigh => format!("{:+} semis", settings.lead_high),
+            Item::ArpPattern => settings.arp_pattern.name().to_string(),
+            Item::ArpRate => settings.arp_rate.abbrev().to_string(),
+            Item::Tempo => format!("{} BPM", settings.tempo),
+            Item::ArpOctaves => format!("{}", settings.arp_octaves),
+            Item::Strum => settings.strum.name().to_string(),
+            Item::StrumTime => format!("{} ms", settings.strum_time),
+            Item::Attack => format!("{} ms", settings.attack),
+            Item::Decay => format!("{} ms", settings.decay),
+            Item::Sustain => format!("{}%", settings.sustain),
+            Item::Release => format!("{} ms", settings.release),
+            Item::Detector => settings.detector.name().to_string(),
+            Item::Fallback => settings.fallback_detector.map(|detector| detector.name()).unwrap_or("Off").to_string(),
+            Item::Power => format!("{:.1e}", settings.power_threshold),
+            Item::Clarity => format!("{:.2}", settings.clarity_threshold),
+            Item::Resonance => format!("Q {:.1}", settings.resonance),
+        }
+    }
+
+    fn adjust(self, settings: &mut Settings, scala_files: &[String], steps: i64) {
+        match self {
+            Item::A4 => {
+                settings.a4 = (settings.a4 + steps as f64).clamp(A4_MIN, A4_MAX);
+            }
+            Item::Temperament => {
+                // the built in temperaments, then every .scl on the drive
+                let mut options = vec![Temperament::Equal, Temperament::Just, Temperament::Pythagorean, Temperament::Edo];
+                options.extend(scala_files.iter().map(|file| Temperament::Scala(file.clone())));
+                let cur_idx = options.iter().position(|option| *option == settings.temperament).unwrap_or(0) as i64;
+                let new_idx = (cur_idx + steps).rem_euclid(options.len() as i64);
+                settings.temperament = options[new_idx as usize].clone();
+            }
+            Item::EdoSteps => {
+                settings.edo_steps = (settings.edo_steps as i64 + steps).clamp(tuning::EDO_MIN as i64, tuning::EDO_MAX as i64) as u16;
+            }
+            Item::LeadLow => {
+                // not clamp, a hand edited settings.ron could have the bounds crossed
+                settings.lead_low = (settings.lead_low + steps as i32).min(settings.lead_high - LEAD_MIN_SPAN).max(LEAD_LOW_MIN);
+            }
+            Item::LeadHigh => {
+                settings.lead_high = (settings.lead_high + steps as i32).max(settings.lead_low + LEAD_MIN_SPAN).min(LEAD_HIGH_MAX);
+            }
+            Item::ArpPattern => {
+                settings.arp_pattern = settings.arp_pattern.next(steps);
+            }
+            Item::ArpRate => {
+                settings.arp_rate = settings.arp_rate.next(steps);
+            }
+            Item::Tempo => {
+                settings.tempo = (settings.tempo as i64 + steps).clamp(arp::TEMPO_MIN as i64, arp::TEMPO_MAX as i64) as u16;
+            }
+            Item::ArpOctaves => {
+                settings.arp_octaves = (settings.arp_octaves as i64 + steps).clamp(1, arp::OCTAVES_MAX as i64) as u16;
+            }
+            Item::Strum => {
+                settings.strum = settings.strum.next(steps);
+            }
+            Item::StrumTime => {
+                settings.strum_time = (settings.strum_time as i64 + steps).clamp(strum::STRUM_TIME_MIN as i64, strum::STRUM_TIME_MAX as i64) as u16;
+            }
+            Item::Attack => {
+                settings.attack = (settings.attack as i64 + steps * ENVELOPE_STEP).clamp(0, envelope::ATTACK_MAX as i64) as u16;
+            }
+            Item::Decay => {
+                settings.decay = (settings.decay as i64 + steps * ENVELOPE_STEP).clamp(0, envelope::DECAY_MAX as i64) as u16;
+            }
+            Item::Sustain => {
+                settings.sustain = (settings.sustain as i64 + steps * 5).clamp(0, 100) as u16;
+            }
+            Item::Release => {
+                settings.release = (settings.release as i64 + steps * ENVELOPE_STEP).clamp(0, envelope::RELEASE_MAX as i64) as u16;
+            }
+            Item::Detector => {
+                settings.detector = settings.detector.next(steps);
+            }
+            Item::Fallback => {
+                // Off, then every detector
+                let mut options = vec![None];
+                options.extend(pitch::DETECTORS.iter().map(|detector| Some(*detector)));
+                let cur_idx = options.iter().position(|option| *option == settings.fallback_detector).unwrap_or(0) as i64;
+                let new_idx = (cur_idx + steps).rem_euclid(options.len() as i64);
+                settings.fallback_detector = options[new_idx as usize];
+            }
+            Item::Power => {
+                // doubles or halves, it's useful over a few orders of magnitude
+                settings.power_threshold = (settings.power_threshold * 2.0_f64.powi(steps as i32)).clamp(pitch::POWER_MIN, pitch::POWER_MAX);
+            }
+            Item::Clarity => {
+                settings.clarity_threshold = (settings.clarity_threshold + steps as f64 * 0.05).clamp(pitch::CLARITY_MIN, pitch::CLARITY_MAX);
+            }
+            Item::Resonance => {
+                settings.resonance = (settings.resonance + steps as f32 * 0.1).clamp(filter::RESONANCE_MIN, filter::RESONANCE_MAX);
+            }
+        }
+    }
+}
+
+// The volume encoder picks the setting, the key encoder changes it and pressing
+// the key encoder saves and leaves. scala_files are the .scl files that can
+// be picked as the tuning. Returns true if anything changed.
+pub fn settings_menu(settings: &mut Settings, scala_files: &[String], enc_a: &impl Encoder, enc_b: &impl Encoder, display: &mut impl Screen) -> bool {
+    // wait for the press that opened the menu to let go
+    while enc_b.is_pressed() {
+        sleep(Duration::from_millis(10));
+    }
+
+    let original = settings.clone();
+    let mut item_idx: i64 = 0;
+    let mut last_cnt_a = enc_a.count();
+    let mut last_cnt_b = enc_b.count();
+    loop {
+        let cur_cnt_a = enc_a.count();
+        if cur_cnt_a != last_cnt_a {
+            item_idx = (item_idx + cur_cnt_a - last_cnt_a).rem_euclid(ITEMS.len() as i64);
+        }
+        last_cnt_a = cur_cnt_a;
+
+        let item = ITEMS[item_idx as usize];
+        let cur_cnt_b = enc_b.count();
+        if cur_cnt_b != last_cnt_b {
+            item.adjust(settings, scala_files, cur_cnt_b - last_cnt_b);
+        }
+        last_cnt_b = cur_cnt_b;
+
+        menu_msg(display, item.name(), &item.value(settings));
+
+        if enc_b.is_pressed() {
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+    while enc_b.is_pressed() {
+        sleep(Duration::from_millis(10));
+    }
+
+    let changed = *settings != original;
+    if changed {
+        settings.save();
+    }
+    changed
+}
+
+pub fn menu_msg<D: Screen>(display: &mut D, name: &str, value: &str) {
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_8X13)
+        .text_color(BinaryColor::On)
+        .build();
+
+    display.clear_buffer();
+
+    let name_x: i32 = 64 - (((name.len() as i32) * 8) / 2);
+    Text::with_baseline(name, Point::new(name_x, 14), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+    let value_x: i32 = 64 - (((value.len() as i32) * 8) / 2);
+    Text::with_baseline(value, Point::new(value_x, 36), text_style, Baseline::Top)
+        .draw(display)
+        .unwrap();
+
+    display.flush().unwrap();
+}
@@ -0,0 +1,63 @@
+// Strumming, the notes of a chord start one after another a few ms apart like
+// a pick going across strings instead of all at once.
+use std::{thread::{sleep, spawn}, time::Duration};
+
+use awedio::{manager::Manager, Sound};
+use serde::{Deserialize, Serialize};
+
+pub const STRUM_TIME_MIN: u16 = 5;
+pub const STRUM_TIME_MAX: u16 = 80;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Strum {
+    #[default]
+    Off,
+    // lowest note first
+    Up,
+    // highest note first
+    Down,
+}
+
+const STRUMS: [Strum; 3] = [Strum::Off, Strum::Up, Strum::Down];
+
+impl Strum {
+    pub fn next(self, steps: i64) -> Self {
+        let cur_idx = STRUMS.iter().position(|strum| *strum == self).unwrap_or(0) as i64;
+        STRUMS[(cur_idx + steps).rem_euclid(STRUMS.len() as i64) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strum::Off => "Off",
+            Strum::Up => "Up",
+            Strum::Down => "Down",
+        }
+    }
+}
+
+// Plays a chord's notes, each with its pitch, strum_time ms apart. The waiting
+// happens on its own thread so the keypad is still read during a slow strum,
+// the notes' controllers are already with the caller so a gate stops any that
+// haven't started yet too.
+pub fn play_strummed<S: Sound + 'static>(manager: &mut Manager, mut notes: Vec<(f64, S)>, strum: Strum, strum_time: u16) {
+    match strum {
+        Strum::Off => {
+            for (_, note) in notes {
+                manager.play(Box::new(note));
+            }
+            return;
+        }
+        Strum::Up => notes.sort_by(|a, b| a.0.total_cmp(&b.0)),
+        Strum::Down => notes.sort_by(|a, b| b.0.total_cmp(&a.0)),
+    }
+    let strum_time = Duration::from_millis(strum_time.clamp(STRUM_TIME_MIN, STRUM_TIME_MAX) as u64);
+    let mut manager = manager.clone();
+    spawn(move || {
+        for (i, (_, note)) in notes.into_iter().enumerate() {
+            if i > 0 {
+                sleep(strum_time);
+            }
+            manager.play(Box::new(note));
+        }
+    });
+}
@@ -1,7 +1,11 @@
-use serde::{Deserialize, Serialize};
-use vl53l1x::{Vl53l1x, Vl53l1xRangeStatus, CalibrationData, CustomerNvmManaged, AdditionalOffsetCalData, OpticalCentre, GainCalibrationData, CalPeakRateMap};
-use rppal::{gpio::{Event, Gpio, Trigger}, i2c::I2c};
-use std::{env, fs::{self, File}, io, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU16}}, thread::sleep, time::Duration};
+#[cfg(feature = "pi")]
+use vl53l1x::{Vl53l1x, Vl53l1xRangeStatus};
+use std::{sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU16}}, thread::{sleep, spawn}, time::Duration};
+
+use crate::{filter::{self, FilterParams}, mixer::{self, Mixer}};
+
+// past this the hand is out of the way and the filter is fully open
+const OPEN_DISTANCE: u16 = 240;
 
 pub enum FilterType {
     HPF,
@@ -9,35 +13,73 @@ pub enum FilterType {
 }
 pub type ROIRight = AtomicBool;
 
-pub fn init_eq() {
-    let _amix_en = std::process::Command::new("amixer")
-        .args(vec!["-c", "1", "cset", "numid=9", "on"])
-        .spawn().expect("Failed to launch amixer!");
-    sleep(std::time::Duration::from_millis(50));
-    for freq in 10..15 {
-        let numid_string = format!("numid={}", freq);
-        let numid= numid_string.as_str();
-        let _amix = std::process::Command::new("amixer")
-            .args(vec!["-c", "1", "cset", numid, "12"])
-            .spawn().expect("Failed to launch amixer!");
+// Ranging interface used by the filter interrupt, the sensor alternates
+// between a left and right region of interest on every reading.
+pub trait TofSensor {
+    // distance in mm, None if the last ranging was not valid
+    fn read_distance(&mut self) -> Option<u16>;
+    fn set_roi(&mut self, right: bool);
+}
+
+#[cfg(feature = "pi")]
+impl TofSensor for Vl53l1x {
+    fn read_distance(&mut self) -> Option<u16> {
+        let sample = self.read_sample().expect("failed to get right sample");
+        //println!("sampled: {}mm ({:#?})", sample.distance, sample.status);
+        match sample.status {
+            Vl53l1xRangeStatus::Ok => Some(sample.distance),
+            _ => None
+        }
+    }
+
+    fn set_roi(&mut self, right: bool) {
+        if right {
+            self.set_user_roi(12, 10, 15, 6).expect("failed to set ROI Right during interrupt");
+        } else {
+            self.set_user_roi(0, 10, 3, 6).expect("failed to set ROI Left during interrupt");
+        }
+    }
+}
+
+// In-memory sensor, clones share the same distance. None reads like nothing
+// being in range of the sensor.
+#[derive(Clone, Default)]
+pub struct MockTof {
+    distance: Arc<Mutex<Option<u16>>>,
+}
+
+impl MockTof {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_distance(&self, distance: Option<u16>) {
+        *self.distance.lock().expect("failed to lock mock TOF") = distance;
     }
 }
 
-fn set_eq(freq: u8, level: i8) {
-    if freq > 5 || freq == 0 || level < 0 {
-        return;
+impl TofSensor for MockTof {
+    fn read_distance(&mut self) -> Option<u16> {
+        *self.distance.lock().expect("failed to lock mock TOF")
     }
-    let numid_string = format!("numid={}", (freq + 9));
-    let numid= numid_string.as_str();
-    let lev_string = level.to_string();
-    let lev = lev_string.as_str();
-    let _amix = std::process::Command::new("amixer")
-        .args(vec!["-q", "-c", "1", "cset", numid, lev])
-        .output();
-    // pray this doesn't cause any issues...
 
+    fn set_roi(&mut self, _right: bool) {}
 }
 
+// The codec's EQ is left flat, the sweep is done by the software filter.
+pub fn init_eq(mixer: &Mutex<Mixer>) {
+    let mut mixer = mixer.lock().expect("failed to lock mixer");
+    if let Err(e) = mixer.set_switch(mixer::EQ_SWITCH, true) {
+        eprintln!("failed to enable EQ: {}", e);
+    }
+    for band in mixer::EQ_BANDS {
+        if let Err(e) = mixer.set_volume(band, 12) {
+            eprintln!("failed to init EQ: {}", e);
+        }
+    }
+}
+
+#[cfg(feature = "pi")]
 pub fn init_tof() -> Vl53l1x {
     let mut tof_sensor = Vl53l1x::new(1, None).expect("Failed to create TOF sensor struct");
     tof_sensor.soft_reset().expect("Failed to reset TOF sensor");
@@ -51,314 +93,58 @@ pub fn init_tof() -> Vl53l1x {
     return tof_sensor;
 }
 
-pub fn tof_eq_int(_event: Event, tof_sensor: Arc<Mutex<Vl53l1x>>, cur_roi: &ROIRight, cur_hpf: Arc<AtomicU16>, cur_lpf: Arc<AtomicU16>, enabled_low: &Arc<AtomicBool>, enabled_high: &Arc<AtomicBool>) {
+pub fn tof_eq_int<T: TofSensor>(tof_sensor: Arc<Mutex<T>>, filter: &FilterParams, cur_roi: &ROIRight, cur_hpf: Arc<AtomicU16>, cur_lpf: Arc<AtomicU16>, enabled_low: &Arc<AtomicBool>, enabled_high: &Arc<AtomicBool>) {
     //println!("TOF interrupt");
     let mut sensor = tof_sensor.lock().expect("failed to acquire sensor lock");
-    let sample = sensor.read_sample().expect("failed to get right sample");
-    //println!("sampled: {}mm ({:#?})", sample.distance, sample.status);
+    let distance = sensor.read_distance();
     let low_enabled = enabled_low.load(std::sync::atomic::Ordering::SeqCst);
     let high_enabled = enabled_high.load(std::sync::atomic::Ordering::SeqCst);
     if low_enabled ||  high_enabled{
-        match sample.status {
-            Vl53l1xRangeStatus::Ok => {
-                let filter_strength: i8 = if sample.distance < 240 {
-                    (sample.distance/20).try_into().unwrap()
-                } else {
-                    12
-                };
+        match distance {
+            Some(distance) => {
+                let distance = distance.min(OPEN_DISTANCE);
                 if cur_roi.load(std::sync::atomic::Ordering::SeqCst) {
-                    set_filter(FilterType::LPF, filter_strength, cur_hpf, cur_lpf);
+                    set_filter(filter, FilterType::LPF, distance, cur_hpf, cur_lpf);
                     if low_enabled {    
                         cur_roi.store(false, std::sync::atomic::Ordering::SeqCst);
-                        sensor.set_user_roi(0, 10, 3, 6).expect("failed to set ROI Left during interrupt");
+                        sensor.set_roi(false);
                     }
                 } else {
-                    set_filter(FilterType::HPF, filter_strength, cur_hpf, cur_lpf);
+                    set_filter(filter, FilterType::HPF, distance, cur_hpf, cur_lpf);
                     if high_enabled {
                         cur_roi.store(true, std::sync::atomic::Ordering::SeqCst);
-                        sensor.set_user_roi(12, 10, 15, 6).expect("failed to set ROI Right during interrupt");
+                        sensor.set_roi(true);
                     }
                 }
             }
-            _ => {}
+            None => {}
         }
     }
 }
 
-fn set_filter(filter: FilterType, strength: i8, cur_hpf: Arc<AtomicU16>, cur_lpf: Arc<AtomicU16>) {
-    match filter {
-        FilterType::LPF => {
-            set_eq(1, strength);
+// For sensors without an interrupt line, runs the filter handler on its own
+// thread at the same rate the VL53L1X is set to range at.
+pub fn poll_tof<T: TofSensor + Send + 'static>(tof_sensor: Arc<Mutex<T>>, filter: Arc<FilterParams>, cur_hpf: Arc<AtomicU16>, cur_lpf: Arc<AtomicU16>, enabled_low: Arc<AtomicBool>, enabled_high: Arc<AtomicBool>) {
+    let cur_roi: ROIRight = ROIRight::new(true);
+    spawn(move || loop {
+        sleep(Duration::from_millis(24));
+        tof_eq_int(tof_sensor.clone(), &filter, &cur_roi, cur_hpf.clone(), cur_lpf.clone(), &enabled_low, &enabled_high);
+    });
+}
 
-            if strength < 12 {
-                set_eq(2, strength/2);
-            } else {
-                set_eq(2, strength);
-            }
-            cur_lpf.store(strength as u16, std::sync::atomic::Ordering::SeqCst);
+// Moves the cutoff continuously with distance, the stored strength is only
+// for the display (0-12 like the old EQ steps).
+fn set_filter(filter: &FilterParams, filter_type: FilterType, distance: u16, cur_hpf: Arc<AtomicU16>, cur_lpf: Arc<AtomicU16>) {
+    let position = distance as f32 / OPEN_DISTANCE as f32;
+    let strength = distance / 20;
+    match filter_type {
+        FilterType::LPF => {
+            filter.set_lpf(filter::sweep(filter::LPF_CLOSED, filter::LPF_OPEN, position));
+            cur_lpf.store(strength, std::sync::atomic::Ordering::SeqCst);
         },
         FilterType::HPF => {
-            set_eq(5, strength);
-
-            if strength < 12 {
-                set_eq(4, strength/2);
-            } else {
-                set_eq(4, strength);
-            }
-            cur_hpf.store(strength as u16, std::sync::atomic::Ordering::SeqCst);
-        }
-    }
-}
-
-pub fn calibration(tof_mut: Arc<Mutex<Vl53l1x>>) {
-    let mut tof = tof_mut.lock().expect("failed to get TOF lock for calibration");
-    
-    println!("Ensure TOF sensor is clear and press ENTER to preform SPAD calibration");
-    io::stdin().read_line(&mut "".to_string()).expect("Failed to read line"); 
-    tof.perform_ref_spad_management().expect("failed SPAD calibration!");
-    
-    tof.set_measurement_timing_budget(30000);
-    tof.set_inter_measurement_period(40);
-    println!("Ensure calibration card is 140mm from sensor and press ENTER to preform offset calibration");
-    io::stdin().read_line(&mut "".to_string()).expect("Failed to read line"); 
-    tof.perform_offset_simple_calibration(140).expect("failed offset calibration!");
-    println!("finished offset calibration");
-    
-    tof.set_measurement_timing_budget(30000);
-    tof.set_inter_measurement_period(40);
-    println!("Ensure calibration card is 600mm from sensor and press ENTER to preform cross-talk calibration");
-    io::stdin().read_line(&mut "".to_string()).expect("Failed to read line"); 
-    tof.perform_single_target_xtalk_calibration(600).expect("failed cross-talk calibration!");
-   println!("finished xtalk calibration");
-
-    let mut cal_data: CalibrationData = CalibrationData::new();
-    //io::stdin().read_line(&mut "".to_string()).expect("Failed to read line"); 
-    tof.get_calibration_data(&mut cal_data).expect("failed to get calibration data from sensor");
-    println!("writing calibration data to disk");
-    let data_file = File::create("calibration.ron").expect("couldn't create calibration data file!");
-    let mut se = ron::Serializer::new(data_file, None).expect("failed to serialize calibration data");
-    CalibrationDataRem::serialize(&cal_data, &mut se);
-}
-
-
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-#[serde(remote = "CalibrationData")]
-#[repr(C)]
-pub struct CalibrationDataRem {
-	struct_version: u32,
-    #[serde(with = "CustomerNvmManagedRem")]
-	customer: CustomerNvmManaged,
-    #[serde(with = "AdditionalOffsetCalDataRem")]
-    add_off_cal_data: AdditionalOffsetCalData,
-    #[serde(with = "OpticalCentreRem")]
-	optical_centre: OpticalCentre,
-    #[serde(with = "GainCalibrationDataRem")]
-	gain_cal: GainCalibrationData,
-    #[serde(with = "CalPeakRateMapRem")]
-	cal_peak_rate_map: CalPeakRateMap,
-}
-
-impl Into<CalibrationData> for CalibrationDataRem {
-    fn into(self) -> CalibrationData {
-        CalibrationData {
-            struct_version: self.struct_version,
-            customer: self.customer.into(),
-            add_off_cal_data: self.add_off_cal_data.into(),
-            optical_centre: self.optical_centre.into(),
-            gain_cal: self.gain_cal.into(),
-            cal_peak_rate_map: self.cal_peak_rate_map.into(),
-        }
-    }
-}
-
-impl From<CalibrationData> for CalibrationDataRem {
-    fn from(value: CalibrationData) -> Self {
-        Self {
-            struct_version: value.struct_version,
-            customer: value.customer.into(),
-            add_off_cal_data: value.add_off_cal_data.into(),
-            optical_centre: value.optical_centre.into(),
-            gain_cal: value.gain_cal.into(),
-            cal_peak_rate_map: value.cal_peak_rate_map.into(),
-        }
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(remote = "CustomerNvmManaged")]
-#[repr(C)]
-pub struct CustomerNvmManagedRem {
-    pub global_config__spad_enables_ref_0: u8,
-    pub global_config__spad_enables_ref_1: u8,
-    pub global_config__spad_enables_ref_2: u8,
-    pub global_config__spad_enables_ref_3: u8,
-    pub global_config__spad_enables_ref_4: u8,
-    pub global_config__spad_enables_ref_5: u8,
-    pub global_config__ref_en_start_select: u8,
-    pub ref_spad_man__num_requested_ref_spads: u8,
-    pub ref_spad_man__ref_location: u8,
-    pub algo__crosstalk_compensation_plane_offset_kcps: u32,
-    pub algo__crosstalk_compensation_x_plane_gradient_kcps: i16,
-    pub algo__crosstalk_compensation_y_plane_gradient_kcps: i16,
-    pub ref_spad_char__total_rate_target_mcps: u16,
-    pub algo__part_to_part_range_offset_mm: i16,
-    pub mm_config__inner_offset_mm: i16,
-    pub mm_config__outer_offset_mm: i16,
-}
-impl Into<CustomerNvmManaged> for CustomerNvmManagedRem {
-    fn into(self) -> CustomerNvmManaged {
-        CustomerNvmManaged {
-            global_config__spad_enables_ref_0: self.global_config__spad_enables_ref_0,
-            global_config__spad_enables_ref_1: self.global_config__spad_enables_ref_1,
-            global_config__spad_enables_ref_2: self.global_config__spad_enables_ref_2,
-            global_config__spad_enables_ref_3: self.global_config__spad_enables_ref_3,
-            global_config__spad_enables_ref_4: self.global_config__spad_enables_ref_4,
-            global_config__spad_enables_ref_5: self.global_config__spad_enables_ref_5,
-            global_config__ref_en_start_select: self.global_config__ref_en_start_select,
-            ref_spad_man__num_requested_ref_spads: self.ref_spad_man__num_requested_ref_spads,
-            ref_spad_man__ref_location: self.ref_spad_man__ref_location,
-            algo__crosstalk_compensation_plane_offset_kcps: self.algo__crosstalk_compensation_plane_offset_kcps,
-            algo__crosstalk_compensation_x_plane_gradient_kcps: self.algo__crosstalk_compensation_x_plane_gradient_kcps,
-            algo__crosstalk_compensation_y_plane_gradient_kcps: self.algo__crosstalk_compensation_y_plane_gradient_kcps,
-            ref_spad_char__total_rate_target_mcps: self.ref_spad_char__total_rate_target_mcps,
-            algo__part_to_part_range_offset_mm: self.algo__part_to_part_range_offset_mm,
-            mm_config__inner_offset_mm: self.mm_config__inner_offset_mm,
-            mm_config__outer_offset_mm: self.mm_config__outer_offset_mm,
-        }
-    }
-}
-impl From<CustomerNvmManaged> for CustomerNvmManagedRem {
-    fn from(value: CustomerNvmManaged) -> Self {
-        Self {
-            global_config__spad_enables_ref_0: value.global_config__spad_enables_ref_0,
-            global_config__spad_enables_ref_1: value.global_config__spad_enables_ref_1,
-            global_config__spad_enables_ref_2: value.global_config__spad_enables_ref_2,
-            global_config__spad_enables_ref_3: value.global_config__spad_enables_ref_3,
-            global_config__spad_enables_ref_4: value.global_config__spad_enables_ref_4,
-            global_config__spad_enables_ref_5: value.global_config__spad_enables_ref_5,
-            global_config__ref_en_start_select: value.global_config__ref_en_start_select,
-            ref_spad_man__num_requested_ref_spads: value.ref_spad_man__num_requested_ref_spads,
-            ref_spad_man__ref_location: value.ref_spad_man__ref_location,
-            algo__crosstalk_compensation_plane_offset_kcps: value.algo__crosstalk_compensation_plane_offset_kcps,
-            algo__crosstalk_compensation_x_plane_gradient_kcps: value.algo__crosstalk_compensation_x_plane_gradient_kcps,
-            algo__crosstalk_compensation_y_plane_gradient_kcps: value.algo__crosstalk_compensation_y_plane_gradient_kcps,
-            ref_spad_char__total_rate_target_mcps: value.ref_spad_char__total_rate_target_mcps,
-            algo__part_to_part_range_offset_mm: value.algo__part_to_part_range_offset_mm,
-            mm_config__inner_offset_mm: value.mm_config__inner_offset_mm,
-            mm_config__outer_offset_mm: value.mm_config__outer_offset_mm,
-        }
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(remote = "AdditionalOffsetCalData")]
-#[repr(C)]
-struct AdditionalOffsetCalDataRem {
-    pub result__mm_inner_actual_effective_spads: u16,
-    pub result__mm_outer_actual_effective_spads: u16,
-    pub result__mm_inner_peak_signal_count_rtn_mcps: u16,
-    pub result__mm_outer_peak_signal_count_rtn_mcps: u16,
-}
-impl Into<AdditionalOffsetCalData> for AdditionalOffsetCalDataRem {
-    fn into(self) -> AdditionalOffsetCalData {
-        AdditionalOffsetCalData {
-            result__mm_inner_actual_effective_spads: self.result__mm_inner_actual_effective_spads,
-            result__mm_outer_actual_effective_spads: self.result__mm_outer_actual_effective_spads,
-            result__mm_inner_peak_signal_count_rtn_mcps: self.result__mm_inner_peak_signal_count_rtn_mcps,
-            result__mm_outer_peak_signal_count_rtn_mcps: self.result__mm_outer_peak_signal_count_rtn_mcps,
-        }
-    }
-}
-impl From<AdditionalOffsetCalData> for AdditionalOffsetCalDataRem {
-    fn from(value: AdditionalOffsetCalData) -> Self {
-        Self {
-            result__mm_inner_actual_effective_spads: value.result__mm_inner_actual_effective_spads,
-            result__mm_outer_actual_effective_spads: value.result__mm_outer_actual_effective_spads,
-            result__mm_inner_peak_signal_count_rtn_mcps: value.result__mm_inner_peak_signal_count_rtn_mcps,
-            result__mm_outer_peak_signal_count_rtn_mcps: value.result__mm_outer_peak_signal_count_rtn_mcps,
-        }
-    }
-}
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(remote = "OpticalCentre")]
-#[repr(C)]
-struct OpticalCentreRem {
-    pub x_centre: u8,
-    pub y_centre: u8,
-}
-impl Into<OpticalCentre> for OpticalCentreRem {
-    fn into(self) -> OpticalCentre {
-        OpticalCentre {
-            x_centre: self.x_centre,
-            y_centre: self.y_centre,
+            filter.set_hpf(filter::sweep(filter::HPF_CLOSED, filter::HPF_OPEN, position));
+            cur_hpf.store(strength, std::sync::atomic::Ordering::SeqCst);
         }
     }
 }
-impl From<OpticalCentre> for OpticalCentreRem {
-    fn from(value: OpticalCentre) -> Self{
-        Self {
-            x_centre: value.x_centre,
-            y_centre: value.y_centre,
-        }
-    }
-} 
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(remote = "GainCalibrationData")]
-#[repr(C)]
-struct GainCalibrationDataRem {
-	pub standard_ranging_gain_factor: u16,
-}
-
-impl Into<GainCalibrationData> for GainCalibrationDataRem {
-    fn into(self) -> GainCalibrationData {
-        GainCalibrationData {
-            standard_ranging_gain_factor: self.standard_ranging_gain_factor
-        }
-    }
-}
-
-impl From<GainCalibrationData> for GainCalibrationDataRem {
-    fn from(value: GainCalibrationData) -> Self {
-        Self {
-            standard_ranging_gain_factor: value.standard_ranging_gain_factor
-        }
-    }
-}
-
-const VL53L1_NVM_PEAK_RATE_MAP_SAMPLES: usize = 25;
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(remote = "CalPeakRateMap")]
-#[repr(C)]
-struct CalPeakRateMapRem {
-    pub cal_distance_mm: i16,
-    pub max_samples: u16,
-    pub width: u16,
-    pub height: u16, 
-    pub peak_rate_mcps: [u16; VL53L1_NVM_PEAK_RATE_MAP_SAMPLES],
-}
-
-impl Into<CalPeakRateMap> for CalPeakRateMapRem {
-    fn into(self) -> CalPeakRateMap {
-        CalPeakRateMap {
-            cal_distance_mm: self.cal_distance_mm,
-            max_samples: self.max_samples,
-            width: self.width,
-            height: self.height, 
-            peak_rate_mcps: self.peak_rate_mcps,
-        }
-    }
-}
-
-impl From<CalPeakRateMap> for CalPeakRateMapRem {
-    fn from(value: CalPeakRateMap) -> Self {
-        Self {
-            cal_distance_mm: value.cal_distance_mm,
-            max_samples: value.max_samples,
-            width: value.width,
-            height: value.height, 
-            peak_rate_mcps: value.peak_rate_mcps,
-        }
-    }
-}
\ No newline at end of file
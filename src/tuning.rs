@@ -0,0 +1,232 @@
+// Interval ratios for the notes of a chord. Everything is worked out relative
+// to the key's root, so the key itself always comes from A4 in equal
+// temperament and the temperament only changes the intervals above it.
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+// 5-limit ratios for the 12 semitones above the root
+const JUST: [f64; 12] = [1.0, 16.0 / 15.0, 9.0 / 8.0, 6.0 / 5.0, 5.0 / 4.0, 4.0 / 3.0, 45.0 / 32.0, 3.0 / 2.0, 8.0 / 5.0, 5.0 / 3.0, 9.0 / 5.0, 15.0 / 8.0];
+// stacked 3:2 fifths, folded back into one octave
+const PYTHAGOREAN: [f64; 12] = [1.0, 256.0 / 243.0, 9.0 / 8.0, 32.0 / 27.0, 81.0 / 64.0, 4.0 / 3.0, 729.0 / 512.0, 3.0 / 2.0, 128.0 / 81.0, 27.0 / 16.0, 16.0 / 9.0, 243.0 / 128.0];
+
+pub const EDO_MIN: u16 = 5;
+pub const EDO_MAX: u16 = 72;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Temperament {
+    Equal,
+    Just,
+    Pythagorean,
+    // equal divisions of the octave, the step count is a separate setting
+    Edo,
+    // file name of a .scl on the media drive, a .kbm with the same name is
+    // used as its keyboard mapping if there is one
+    Scala(String),
+}
+
+impl Temperament {
+    pub fn name(&self) -> String {
+        match self {
+            Temperament::Equal => "12-TET".to_string(),
+            Temperament::Just => "Just".to_string(),
+            Temperament::Pythagorean => "Pythag".to_string(),
+            Temperament::Edo => "EDO".to_string(),
+            Temperament::Scala(file) => file.trim_end_matches(".scl").to_string(),
+        }
+    }
+}
+
+// A parsed .scl, ratios for degrees 1 to n where the last one is the period
+// (usually 2/1). Degree 0 is always 1/1 and isn't listed in the file.
+#[derive(Clone, Debug)]
+struct ScalaScale {
+    ratios: Vec<f64>,
+    // which scale degree each semitone above the root plays, None if unmapped
+    mapping: Option<KeyboardMapping>,
+}
+
+#[derive(Clone, Debug)]
+struct KeyboardMapping {
+    degrees: Vec<Option<i64>>,
+    // scale degree one repeat of the mapping moves up by
+    octave_degree: i64,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Table([f64; 12]),
+    Edo(u16),
+    Scala(ScalaScale),
+}
+
+#[derive(Clone, Debug)]
+pub struct Tuning {
+    pub a4: f64,
+    // short name for the screen
+    pub name: String,
+    kind: Kind,
+}
+
+impl Tuning {
+    pub fn equal(a4: f64) -> Self {
+        Self {
+            a4,
+            name: Temperament::Equal.name(),
+            kind: Kind::Edo(12),
+        }
+    }
+
+    // Scala files are looked up in media_path, anything that can't be read
+    // comes back as an error so the caller can say so and fall back.
+    pub fn new(a4: f64, temperament: &Temperament, edo_steps: u16, media_path: &Path) -> Result<Self, String> {
+        let kind = match temperament {
+            Temperament::Equal => Kind::Edo(12),
+            Temperament::Just => Kind::Table(JUST),
+            Temperament::Pythagorean => Kind::Table(PYTHAGOREAN),
+            Temperament::Edo => Kind::Edo(edo_steps.clamp(EDO_MIN, EDO_MAX)),
+            Temperament::Scala(file) => {
+                let scl_path = media_path.join(file);
+                let scl_string = fs::read_to_string(&scl_path).map_err(|e| format!("failed to read {:?}: {}", scl_path, e))?;
+                let ratios = parse_scl(&scl_string)?;
+                let kbm_path = scl_path.with_extension("kbm");
+                let mapping = match fs::read_to_string(&kbm_path) {
+                    Ok(kbm_string) => parse_kbm(&kbm_string, ratios.len())?,
+                    Err(_) => None
+                };
+                Kind::Scala(ScalaScale { ratios, mapping })
+            }
+        };
+        let name = match kind {
+            Kind::Edo(steps) if *temperament == Temperament::Edo => format!("{}-EDO", steps),
+            _ => temperament.name()
+        };
+        Ok(Self { a4, name, kind })
+    }
+
+    // Ratio for an interval given in 12-TET semitones above the root.
+    pub fn ratio(&self, semitones: i32) -> f64 {
+        match &self.kind {
+            Kind::Table(table) => {
+                table[semitones.rem_euclid(12) as usize] * 2.0_f64.powi(semitones.div_euclid(12))
+            }
+            Kind::Edo(steps) => {
+                let steps = *steps as f64;
+                2.0_f64.powf((semitones as f64 * steps / 12.0).round() / steps)
+            }
+            Kind::Scala(scale) => {
+                match scale.degree_for(semitones as i64) {
+                    Some(degree) => scale.degree_ratio(degree),
+                    // unmapped keys stay in 12-TET rather than going silent
+                    None => 2.0_f64.powf(semitones as f64 / 12.0)
+                }
+            }
+        }
+    }
+
+    // Ratio for the step-th note of a scale given as semitones above the root
+    // within one octave. A Scala scale that isn't 12 notes and has no keyboard
+    // mapping takes the place of the scale, its own degrees are used instead.
+    pub fn step_ratio(&self, step: usize, scale: &[i32]) -> f64 {
+        match &self.kind {
+            Kind::Scala(scl) if scl.mapping.is_none() && scl.ratios.len() != 12 => {
+                scl.degree_ratio(step as i64)
+            }
+            _ => {
+                let semitones = scale[step % scale.len()] + 12 * (step / scale.len()) as i32;
+                self.ratio(semitones)
+            }
+        }
+    }
+}
+
+impl ScalaScale {
+    fn degree_ratio(&self, degree: i64) -> f64 {
+        let notes = self.ratios.len() as i64;
+        let period = self.ratios[self.ratios.len() - 1];
+        let base = match degree.rem_euclid(notes) {
+            0 => 1.0,
+            d => self.ratios[(d - 1) as usize]
+        };
+        base * period.powi(degree.div_euclid(notes) as i32)
+    }
+
+    fn degree_for(&self, semitones: i64) -> Option<i64> {
+        match &self.mapping {
+            Some(mapping) => {
+                let size = mapping.degrees.len() as i64;
+                let degree = mapping.degrees[semitones.rem_euclid(size) as usize]?;
+                Some(degree + semitones.div_euclid(size) * mapping.octave_degree)
+            }
+            // without a mapping the keys walk up the scale one degree at a time
+            None => Some(semitones)
+        }
+    }
+}
+
+fn pitch_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().filter(|line| !line.trim_start().starts_with('!'))
+}
+
+// http://www.huygens-fokker.org/scala/scl_format.html
+fn parse_scl(text: &str) -> Result<Vec<f64>, String> {
+    let mut lines = pitch_lines(text);
+    let _description = lines.next().ok_or("empty .scl file")?;
+    let count: usize = lines.next()
+        .and_then(|line| line.split_whitespace().next())
+        .and_then(|count| count.parse().ok())
+        .ok_or("missing note count in .scl file")?;
+    let mut ratios = Vec::with_capacity(count);
+    for line in lines.take(count) {
+        let pitch = line.split_whitespace().next().ok_or("blank pitch in .scl file")?;
+        let ratio = if pitch.contains('.') {
+            let cents: f64 = pitch.parse().map_err(|_| format!("bad cents value '{}'", pitch))?;
+            2.0_f64.powf(cents / 1200.0)
+        } else {
+            let (num, den) = pitch.split_once('/').unwrap_or((pitch, "1"));
+            let num: f64 = num.parse().map_err(|_| format!("bad ratio '{}'", pitch))?;
+            let den: f64 = den.parse().map_err(|_| format!("bad ratio '{}'", pitch))?;
+            num / den
+        };
+        if ratio <= 0.0 || !ratio.is_finite() {
+            return Err(format!("bad pitch '{}'", pitch));
+        }
+        ratios.push(ratio);
+    }
+    if ratios.len() != count || count == 0 {
+        return Err(format!(".scl file lists {} notes but has {}", count, ratios.len()));
+    }
+    Ok(ratios)
+}
+
+// http://www.huygens-fokker.org/scala/help.htm#mappings
+// Only the mapping itself is used, the reference note and frequency are
+// replaced by the key and A4 set on the device. A size of 0 is a linear
+// mapping, the same as having no .kbm at all.
+fn parse_kbm(text: &str, scale_size: usize) -> Result<Option<KeyboardMapping>, String> {
+    let fields: Vec<&str> = pitch_lines(text)
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    if fields.len() < 7 {
+        return Err("incomplete .kbm header".to_string());
+    }
+    let size: usize = fields[0].parse().map_err(|_| "bad .kbm map size")?;
+    if size == 0 {
+        return Ok(None);
+    }
+    let octave_degree: i64 = match fields[6].parse().map_err(|_| "bad .kbm octave degree")? {
+        // 0 means the scale's own period
+        0 => scale_size as i64,
+        degree => degree
+    };
+    let mut degrees = Vec::with_capacity(size);
+    for field in fields.iter().skip(7).take(size) {
+        degrees.push(match *field {
+            "x" | "X" => None,
+            degree => Some(degree.parse().map_err(|_| format!("bad .kbm degree '{}'", degree))?)
+        });
+    }
+    // missing entries at the end are unmapped
+    degrees.resize(size, None);
+    Ok(Some(KeyboardMapping { degrees, octave_degree }))
+}
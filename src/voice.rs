@@ -0,0 +1,106 @@
+// Voices the notes are played on. Every note takes one, so there is a fixed
+// limit on how many play at once no matter how long the samples are, and a
+// gate always reaches every note that's still sounding.
+use std::{sync::{Arc, atomic::AtomicBool, mpsc::{Receiver, TryRecvError}}, time::Instant};
+
+use awedio::{sounds::wrappers::{AdjustableSpeed, CompletionNotifier, Controllable, Controller, Stoppable}, Sound};
+
+use crate::{envelope::{Adsr, Envelope}, sample::{Sample, SampleVoice}};
+
+// a 9th chord with a slash bass strummed over the tail of the last one
+pub const MAX_VOICES: usize = 16;
+
+pub type VoiceSound = CompletionNotifier<Controllable<Stoppable<Envelope<AdjustableSpeed<SampleVoice>>>>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VoiceState {
+    // its key is down (or it's held with #)
+    Held,
+    // gated, fading out over the envelope's release
+    Released,
+}
+
+struct Voice {
+    // only used to cut a voice off when it's stolen
+    controller: Controller<Stoppable<Envelope<AdjustableSpeed<SampleVoice>>>>,
+    // starts the envelope's release and ends the sustain loop
+    released: Arc<AtomicBool>,
+    state: VoiceState,
+    started: Instant,
+    // hears from the sound (or is hung up on) once it has finished
+    finished: Receiver<()>,
+}
+
+impl Voice {
+    fn is_finished(&self) -> bool {
+        !matches!(self.finished.try_recv(), Err(TryRecvError::Empty))
+    }
+}
+
+pub struct VoiceManager {
+    voices: Vec<Voice>,
+    max_voices: usize,
+    adsr: Adsr,
+}
+
+impl VoiceManager {
+    pub fn new(max_voices: usize, adsr: Adsr) -> Self {
+        Self {
+            voices: Vec::new(),
+            max_voices: max_voices.max(1),
+            adsr,
+        }
+    }
+
+    // for voices started from now on, the ones already playing keep theirs
+    pub fn set_envelope(&mut self, adsr: Adsr) {
+        self.adsr = adsr;
+    }
+
+    // A new voice playing sample at speed, stealing one if they're all in use.
+    // With looping it goes round the sample's sustain loop until it's released.
+    // The caller starts the returned sound, now or later for a strum.
+    pub fn voice(&mut self, sample: &Sample, speed: f64, looping: bool) -> VoiceSound {
+        self.reap();
+        while self.voices.len() >= self.max_voices {
+            self.steal();
+        }
+        let released = Arc::new(AtomicBool::new(false));
+        let play_snd = Envelope::new(sample.voice(released.clone(), looping, speed).with_adjustable_speed_of(speed as f32), self.adsr, released.clone());
+        let (play_snd, controller) = play_snd.stoppable().controllable();
+        let (play_snd, finished) = play_snd.with_completion_notifier();
+        self.voices.push(Voice {
+            controller,
+            released,
+            state: VoiceState::Held,
+            started: Instant::now(),
+            finished,
+        });
+        play_snd
+    }
+
+    // Gates every voice that's still held, they fade out and finish on their own.
+    pub fn release_all(&mut self) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.state == VoiceState::Held) {
+            voice.released.store(true, std::sync::atomic::Ordering::SeqCst);
+            voice.state = VoiceState::Released;
+        }
+        self.reap();
+    }
+
+    fn reap(&mut self) {
+        self.voices.retain(|voice| !voice.is_finished());
+    }
+
+    // Released voices are on their way out and the quietest, so the oldest of
+    // those goes first, then the oldest held one.
+    fn steal(&mut self) {
+        let victim = self.voices.iter().enumerate()
+            .min_by_key(|(_, voice)| (voice.state == VoiceState::Held, voice.started))
+            .map(|(i, _)| i);
+        if let Some(victim) = victim {
+            let mut voice = self.voices.remove(victim);
+            voice.controller.set_stopped();
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// Renders small scripts end to end, the WAV that comes out has to be at the
+// device's rate, as long as the notes in it and at the pitches they're meant to
+// be, so a change to the pitch math shows up here.
+use std::{f64::consts::PI, fs, path::PathBuf};
+
+use chrustler::render::render_file;
+
+// somewhere of its own for each test's sample, script and output
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chrustler-render-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).expect("couldn't make a scratch directory");
+    dir
+}
+
+fn write_sine(path: &PathBuf, freq: f64, seconds: f64, sample_rate: u32) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).expect("couldn't write the sample");
+    for i in 0..(seconds * sample_rate as f64) as usize {
+        let s = 0.5 * (2.0 * PI * freq * i as f64 / sample_rate as f64).sin();
+        writer.write_sample((s * i16::MAX as f64) as i16).expect("couldn't write the sample");
+    }
+    writer.finalize().expect("couldn't write the sample");
+}
+
+// the rendered WAV's spec and its samples, from a sine at sample_freq
+fn render(name: &str, sample_freq: f64, sample_rate: u32, script: &str) -> (hound::WavSpec, Vec<f64>) {
+    let dir = scratch_dir(name);
+    write_sine(&dir.join("sine.wav"), sample_freq, 0.5, sample_rate);
+    let script_path = dir.join("script.ron");
+    fs::write(&script_path, script).expect("couldn't write the script");
+    let out_path = dir.join("out.wav");
+    render_file(&script_path, &out_path).expect("render failed");
+    let mut reader = hound::WavReader::open(&out_path).expect("couldn't read the render");
+    let samples = reader.samples::<i16>().map(|s| s.expect("couldn't read the render") as f64 / 32768.0).collect();
+    let rendered = (reader.spec(), samples);
+    let _ = fs::remove_dir_all(&dir);
+    rendered
+}
+
+// amplitude of the render at one frequency
+fn level(samples: &[f64], freq: f64) -> f64 {
+    let step = 2.0 * PI * freq / 48000.0;
+    let (re, im) = samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, s)| {
+        (re + s * (step * i as f64).cos(), im + s * (step * i as f64).sin())
+    });
+    2.0 * (re * re + im * im).sqrt() / samples.len() as f64
+}
+
+// Checks the render has these notes in it and no others, looking at every
+// semitone from A2 to A6. The start and end are left out where the notes
+// come in and go.
+fn assert_notes(samples: &[f64], expected: &[f64]) {
+    let middle = &samples[1200..samples.len() - 1200];
+    for semitone in -24..24 {
+        let freq = 440.0 * 2.0_f64.powf(semitone as f64 / 12.0);
+        let level = level(middle, freq);
+        if expected.iter().any(|note| (note - freq).abs() < 0.5) {
+            assert!(level > 0.2, "{:.1}Hz is missing, level {:.3}", freq, level);
+        } else {
+            assert!(level < 0.05, "{:.1}Hz shouldn't be there, level {:.3}", freq, level);
+        }
+    }
+}
+
+// the root alone at the sample's own pitch plays it through once
+const ROOT_SCRIPT: &str = r#"(sample: "sine.wav", freq: Some(440.0), key: A, octave: MID, chord_type: 3,
+    attack: 0, decay: 0, sustain: 100, release: 10, chords: [(start: 0.0, degree: 0)])"#;
+
+#[test]
+fn renders_at_the_device_rate() {
+    let (spec, samples) = render("root", 440.0, 48000, ROOT_SCRIPT);
+    let len = samples.len();
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.channels, 1);
+    assert_eq!(spec.bits_per_sample, 16);
+    // half a second, give or take the last batch
+    assert!((24000..24000 + 512).contains(&len), "rendered {} samples", len);
+}
+
+// C in the middle octave from an E sample, moved down by its speed alone
+#[test]
+fn plays_the_key_from_any_root() {
+    let script = r#"(sample: "sine.wav", freq: Some(329.63), key: C, octave: MID, chord_type: 1,
+        attack: 0, decay: 0, sustain: 100, release: 10, chords: [(start: 0.0, degree: 0)])"#;
+    let (_, samples) = render("key", 329.63, 48000, script);
+    assert_notes(&samples, &[261.63]);
+}
+
+// G B D for the V chord in C major, an octave up in the high octave
+#[test]
+fn plays_the_degree_triad() {
+    let script = r#"(sample: "sine.wav", freq: Some(440.0), key: C, octave: MID, chord_type: 3,
+        attack: 0, decay: 0, sustain: 100, release: 10, chords: [(start: 0.0, degree: 5)])"#;
+    let (_, samples) = render("triad", 440.0, 48000, script);
+    assert_notes(&samples, &[392.0, 493.88, 587.33]);
+    let (_, samples) = render("high", 440.0, 48000, &script.replace("MID", "HIGH"));
+    assert_notes(&samples, &[783.99, 987.77, 1174.66]);
+}
+
+#[test]
+fn resamples_a_44k_sample() {
+    let (spec, samples) = render("44k", 440.0, 44100, ROOT_SCRIPT);
+    let len = samples.len();
+    assert_eq!(spec.sample_rate, 48000);
+    assert!((23900..24000 + 512).contains(&len), "rendered {} samples", len);
+}